@@ -1,4 +1,7 @@
+#![allow(clippy::needless_return)]
+
 use std::{
+    collections::{HashMap, VecDeque},
     fs::File,
     io::{BufRead, BufReader},
 };
@@ -10,90 +13,261 @@ fn main() {
     let file = File::open("input").unwrap();
     let lines = BufReader::new(file).lines();
 
+    let digits = DigitScanner::with_number_words(&[]);
+    let english = DigitScanner::english();
+
     for line in lines {
-        sum_part_one += line
-            .as_ref()
-            .unwrap()
-            .chars()
-            .find_map(|c| c.to_digit(10))
-            .unwrap()
-            * 10
-            + line
-                .as_ref()
-                .unwrap()
-                .chars()
-                .rev()
-                .find_map(|c| c.to_digit(10))
-                .unwrap();
-
-        sum_part_two += callibration_number(&line.unwrap());
+        let line = line.unwrap();
+
+        sum_part_one += digits.calibration_value(&line);
+        sum_part_two += english.calibration_value(&line);
     }
 
     println!("{}", sum_part_one);
     println!("{}", sum_part_two);
 }
 
-fn callibration_number(line: &str) -> u32 {
-    let digit_words: Vec<(String, usize)> = vec![
-        "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
-    ]
-    .into_iter()
-    .enumerate()
-    .map(|(i, w)| (String::from(w), i))
-    .collect();
+const ENGLISH: [(&str, u32); 10] = [
+    ("zero", 0),
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+];
 
-    let first_digit = find_digit(line.chars().collect(), &digit_words);
+// Aho-Corasick automaton over the digit vocabulary. It reads a line once and
+// reports every word in it, including overlapping ones such as the "eight"
+// and "two" in "eightwo".
+struct DigitScanner {
+    transitions: Vec<HashMap<char, usize>>,
+    fail: Vec<usize>,
+    // Every (digit, word length) ending in a state, including the ones
+    // reached through fail links.
+    outputs: Vec<Vec<(u32, usize)>>,
+}
 
-    let reversed_words = digit_words
-        .into_iter()
-        .map(|(w, i)| (w.chars().rev().collect::<String>(), i))
-        .collect();
+impl DigitScanner {
+    fn new(words: &[(&str, u32)]) -> Self {
+        let mut scanner = DigitScanner {
+            transitions: vec![HashMap::new()],
+            fail: vec![0],
+            outputs: vec![Vec::new()],
+        };
 
-    let last_digit = find_digit(line.chars().rev().collect(), &reversed_words);
+        for (word, digit) in words {
+            let mut state = 0;
+            for c in word.chars() {
+                state = match scanner.transitions[state].get(&c) {
+                    Some(next) => *next,
+                    None => {
+                        scanner.transitions.push(HashMap::new());
+                        scanner.fail.push(0);
+                        scanner.outputs.push(Vec::new());
 
-    return first_digit * 10 + last_digit;
-}
+                        let next = scanner.transitions.len() - 1;
+                        scanner.transitions[state].insert(c, next);
+                        next
+                    }
+                };
+            }
 
-fn find_digit(line: Vec<char>, digit_words: &Vec<(String, usize)>) -> u32 {
-    for i in 0..line.len() {
-        let chr = line[i];
-        if chr.is_numeric() {
-            return chr.to_digit(10).unwrap();
+            scanner.outputs[state].push((*digit, word.chars().count()));
         }
 
-        for word in digit_words {
-            let mut found = true;
-            for (j, word_char) in word.0.chars().enumerate() {
-                if line[i + j] != word_char {
-                    found = false;
-                    break;
-                }
+        let mut q: VecDeque<usize> = scanner.transitions[0].values().copied().collect();
+        while let Some(state) = q.pop_front() {
+            let edges: Vec<(char, usize)> = scanner.transitions[state]
+                .iter()
+                .map(|(c, next)| (*c, *next))
+                .collect();
+
+            for (c, next) in edges {
+                let fail = scanner.step(scanner.fail[state], c);
+                scanner.fail[next] = fail;
+
+                let inherited = scanner.outputs[fail].clone();
+                scanner.outputs[next].extend(inherited);
+                q.push_back(next);
+            }
+        }
+
+        return scanner;
+    }
+
+    // The digits themselves, plus every (word, digit) pair. A digit can have
+    // any number of words, or none.
+    fn with_number_words(number_words: &[(&str, u32)]) -> Self {
+        let characters: Vec<String> = (0..10).map(|digit| digit.to_string()).collect();
+        let mut words: Vec<(&str, u32)> = characters
+            .iter()
+            .enumerate()
+            .map(|(digit, c)| (c.as_str(), digit as u32))
+            .collect();
+        words.extend_from_slice(number_words);
+
+        return DigitScanner::new(&words);
+    }
+
+    fn english() -> Self {
+        return DigitScanner::with_number_words(&ENGLISH);
+    }
+
+    fn step(&self, mut state: usize, c: char) -> usize {
+        loop {
+            if let Some(next) = self.transitions[state].get(&c) {
+                return *next;
             }
 
-            if found {
-                return word.1.try_into().unwrap();
+            if state == 0 {
+                return 0;
             }
+            state = self.fail[state];
         }
     }
 
-    return 0;
+    // Every (start, digit) in the line, ordered by where the word ends.
+    #[cfg(test)]
+    fn matches(&self, line: &str) -> Vec<(usize, u32)> {
+        let mut matches = Vec::new();
+        let mut state = 0;
+
+        for (i, c) in line.chars().enumerate() {
+            state = self.step(state, c);
+            for (digit, length) in &self.outputs[state] {
+                matches.push((i + 1 - length, *digit));
+            }
+        }
+
+        return matches;
+    }
+
+    // The digits of the words starting first and last in the line. When two
+    // words start at the same place, the longer one wins.
+    fn first_and_last(&self, line: &str) -> Option<(u32, u32)> {
+        let mut state = 0;
+        let mut first: Option<(usize, usize, u32)> = None;
+        let mut last: Option<(usize, usize, u32)> = None;
+
+        for (i, c) in line.chars().enumerate() {
+            state = self.step(state, c);
+            for (digit, length) in &self.outputs[state] {
+                let start = i + 1 - length;
+
+                if first.is_none_or(|(s, l, _)| start < s || (start == s && *length > l)) {
+                    first = Some((start, *length, *digit));
+                }
+                if last.is_none_or(|(s, l, _)| start > s || (start == s && *length > l)) {
+                    last = Some((start, *length, *digit));
+                }
+            }
+        }
+
+        return Some((first?.2, last?.2));
+    }
+
+    fn calibration_value(&self, line: &str) -> u32 {
+        return match self.first_and_last(line) {
+            Some((first, last)) => first * 10 + last,
+            None => 0,
+        };
+    }
 }
 
 #[cfg(test)]
-
 mod tests {
-    use crate::callibration_number;
+    use crate::DigitScanner;
 
     #[test]
     fn callibration_number_test() {
-        assert_eq!(callibration_number("12"), 12);
-        assert_eq!(callibration_number("zero4"), 4);
-        assert_eq!(callibration_number("one"), 11);
-        assert_eq!(callibration_number("twothree"), 23);
-        assert_eq!(callibration_number("fourfive"), 45);
-        assert_eq!(callibration_number("xdssix1df2fds3sevenasd"), 67);
-        assert_eq!(callibration_number("xxxnineightxxx"), 98);
-        assert_eq!(callibration_number("p7oneasd23asdftwo5dsf"), 75);
-        assert_eq!(callibration_number("6fivefourthreezero"), 60);
+        let english = DigitScanner::english();
+
+        assert_eq!(english.calibration_value("12"), 12);
+        assert_eq!(english.calibration_value("zero4"), 4);
+        assert_eq!(english.calibration_value("one"), 11);
+        assert_eq!(english.calibration_value("twothree"), 23);
+        assert_eq!(english.calibration_value("fourfive"), 45);
+        assert_eq!(english.calibration_value("xdssix1df2fds3sevenasd"), 67);
+        assert_eq!(english.calibration_value("xxxnineightxxx"), 98);
+        assert_eq!(english.calibration_value("p7oneasd23asdftwo5dsf"), 75);
+        assert_eq!(english.calibration_value("6fivefourthreezero"), 60);
+    }
+
+    #[test]
+    fn overlapping_words() {
+        let english = DigitScanner::english();
+
+        assert_eq!(english.calibration_value("eightwo"), 82);
+        assert_eq!(english.calibration_value("oneight"), 18);
+        assert_eq!(english.calibration_value("twone"), 21);
+        assert_eq!(english.calibration_value("sevenine"), 79);
+        assert_eq!(english.calibration_value("ninine"), 99);
+
+        assert_eq!(english.matches("eightwo"), vec![(0, 8), (4, 2)]);
+        assert_eq!(
+            english.matches("xoneightwo1"),
+            vec![(1, 1), (3, 8), (7, 2), (10, 1)]
+        );
+    }
+
+    #[test]
+    fn prefix_at_end_of_line() {
+        let english = DigitScanner::english();
+
+        assert_eq!(english.calibration_value("7tw"), 77);
+        assert_eq!(english.calibration_value("onetw"), 11);
+        assert_eq!(english.calibration_value("seve"), 0);
+        assert_eq!(english.calibration_value(""), 0);
+    }
+
+    #[test]
+    fn suffix_words() {
+        let scanner = DigitScanner::new(&[("abcd", 1), ("bc", 2), ("c", 3)]);
+
+        assert_eq!(scanner.matches("abcd"), vec![(1, 2), (2, 3), (0, 1)]);
+        assert_eq!(scanner.first_and_last("abcd"), Some((1, 3)));
+        assert_eq!(scanner.first_and_last("xbcx"), Some((2, 3)));
+        assert_eq!(scanner.first_and_last("xyz"), None);
+    }
+
+    #[test]
+    fn other_vocabularies() {
+        let german = DigitScanner::with_number_words(&[
+            ("null", 0),
+            ("eins", 1),
+            ("zwei", 2),
+            ("drei", 3),
+            ("vier", 4),
+            ("fünf", 5),
+            ("sechs", 6),
+            ("sieben", 7),
+            ("acht", 8),
+            ("neun", 9),
+            ("zwo", 2),
+        ]);
+        assert_eq!(german.calibration_value("zweiundvierzig"), 24);
+        assert_eq!(german.calibration_value("fünfzehn"), 55);
+        assert_eq!(german.calibration_value("achtzehneins"), 81);
+        assert_eq!(german.calibration_value("eightwo"), 0);
+        assert_eq!(german.calibration_value("zwoundzwanzig"), 22);
+        assert_eq!(german.calibration_value("einszwo"), 12);
+
+        let digits_only = DigitScanner::with_number_words(&[]);
+        assert_eq!(digits_only.calibration_value("eight2three7two"), 27);
+    }
+
+    #[test]
+    fn long_line() {
+        let english = DigitScanner::english();
+
+        let mut line = String::from("x3");
+        line.push_str(&"twe".repeat(200_000));
+        line.push_str("eightw");
+
+        assert_eq!(english.calibration_value(&line), 38);
     }
 }
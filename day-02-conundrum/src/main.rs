@@ -1,45 +1,81 @@
+#![allow(clippy::needless_return)]
+
 use std::{
     cmp::max,
+    collections::BTreeMap,
+    env,
     fs::File,
     io::{BufRead, BufReader},
     str::FromStr,
-    u32,
 };
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
 struct Draw {
-    red: u32,
-    green: u32,
-    blue: u32,
+    cubes: BTreeMap<String, u32>,
 }
 
 impl Draw {
+    #[cfg(test)]
     fn new(red: u32, green: u32, blue: u32) -> Draw {
-        return Draw { red, green, blue };
+        let mut draw = Draw::default();
+        draw.set("red", red);
+        draw.set("green", green);
+        draw.set("blue", blue);
+
+        return draw;
+    }
+
+    fn get(&self, color: &str) -> u32 {
+        return self.cubes.get(color).copied().unwrap_or(0);
+    }
+
+    fn set(&mut self, color: &str, count: u32) {
+        if count == 0 {
+            self.cubes.remove(color);
+        } else {
+            self.cubes.insert(color.to_string(), count);
+        }
+    }
+
+    fn total(&self) -> u32 {
+        return self.cubes.values().sum();
     }
 
     fn is_possible(&self, red: u32, green: u32, blue: u32) -> bool {
-        return self.red <= red && self.green <= green && self.blue <= blue;
+        return self.get("red") <= red && self.get("green") <= green && self.get("blue") <= blue;
     }
 
     fn power(&self) -> u32 {
-        return self.red * self.green * self.blue;
+        return self.get("red") * self.get("green") * self.get("blue");
+    }
+
+    fn to_json(&self) -> String {
+        let colors = self
+            .cubes
+            .iter()
+            .map(|(color, count)| format!("\"{}\":{}", color, count))
+            .collect::<Vec<String>>()
+            .join(",");
+
+        return format!("{{{}}}", colors);
     }
 }
 
 #[derive(Debug)]
 struct DrawParsingError;
 
+fn is_color_name(s: &str) -> bool {
+    return !s.is_empty() && s.chars().all(|ch| ch.is_ascii_lowercase());
+}
+
 impl FromStr for Draw {
     type Err = DrawParsingError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut red = 0;
-        let mut green = 0;
-        let mut blue = 0;
+        let mut draw = Draw::default();
 
         for color in s.split(", ") {
-            let mut split = color.split(" ");
+            let mut split = color.split(' ');
             let number = split
                 .next()
                 .unwrap()
@@ -48,18 +84,14 @@ impl FromStr for Draw {
 
             let color_str = split.next().ok_or(DrawParsingError)?;
 
-            if color_str.contains("red") {
-                red = number;
-            } else if color_str.contains("blue") {
-                blue = number;
-            } else if color_str.contains("green") {
-                green = number;
-            } else {
+            if !is_color_name(color_str) || split.next().is_some() {
                 return Err(DrawParsingError);
             }
+
+            draw.set(color_str, draw.get(color_str) + number);
         }
 
-        return Ok(Draw::new(red, green, blue));
+        return Ok(draw);
     }
 }
 
@@ -82,17 +114,27 @@ impl Game {
     }
 
     fn get_minimum(&self) -> Draw {
-        let mut red_max = 0;
-        let mut green_max = 0;
-        let mut blue_max = 0;
+        let mut minimum = Draw::default();
 
         for draw in &self.draws {
-            red_max = max(red_max, draw.red);
-            green_max = max(green_max, draw.green);
-            blue_max = max(blue_max, draw.blue);
+            for (color, count) in &draw.cubes {
+                minimum.set(color, max(minimum.get(color), *count));
+            }
         }
 
-        return Draw::new(red_max, green_max, blue_max);
+        return minimum;
+    }
+
+    fn satisfies(&self, constraints: &[Constraint]) -> bool {
+        let minimum = self.get_minimum();
+
+        return constraints
+            .iter()
+            .all(|constraint| constraint.matches(minimum.get(&constraint.color)));
+    }
+
+    fn max_draw(&self) -> Option<&Draw> {
+        return self.draws.iter().max_by_key(|draw| draw.total());
     }
 }
 
@@ -126,6 +168,159 @@ impl FromStr for Game {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Comparison {
+    AtMost,
+    AtLeast,
+    Exactly,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Constraint {
+    color: String,
+    comparison: Comparison,
+    count: u32,
+}
+
+impl Constraint {
+    fn new(color: &str, comparison: Comparison, count: u32) -> Constraint {
+        return Constraint {
+            color: color.to_string(),
+            comparison,
+            count,
+        };
+    }
+
+    fn matches(&self, count: u32) -> bool {
+        return match self.comparison {
+            Comparison::AtMost => count <= self.count,
+            Comparison::AtLeast => count >= self.count,
+            Comparison::Exactly => count == self.count,
+        };
+    }
+}
+
+#[derive(Debug)]
+struct ConstraintParsingError;
+
+impl FromStr for Constraint {
+    type Err = ConstraintParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (color, comparison, count) = if let Some((color, count)) = s.split_once("<=") {
+            (color, Comparison::AtMost, count)
+        } else if let Some((color, count)) = s.split_once(">=") {
+            (color, Comparison::AtLeast, count)
+        } else if let Some((color, count)) = s.split_once('=') {
+            (color, Comparison::Exactly, count)
+        } else {
+            return Err(ConstraintParsingError);
+        };
+
+        let color = color.trim();
+        if !is_color_name(color) {
+            return Err(ConstraintParsingError);
+        }
+
+        let count = count
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| ConstraintParsingError)?;
+
+        return Ok(Constraint::new(color, comparison, count));
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Default)]
+struct ColorStats {
+    total: u32,
+    draws: u32,
+    games: u32,
+    max: u32,
+}
+
+struct GameQuery<'a> {
+    games: &'a [Game],
+}
+
+impl<'a> GameQuery<'a> {
+    fn new(games: &'a [Game]) -> GameQuery<'a> {
+        return GameQuery { games };
+    }
+
+    fn filter(&self, constraints: &[Constraint]) -> Vec<&'a Game> {
+        return self
+            .games
+            .iter()
+            .filter(|game| game.satisfies(constraints))
+            .collect();
+    }
+
+    fn color_stats(&self) -> BTreeMap<String, ColorStats> {
+        let mut stats: BTreeMap<String, ColorStats> = BTreeMap::new();
+
+        for game in self.games {
+            for draw in &game.draws {
+                for (color, count) in &draw.cubes {
+                    let entry = stats.entry(color.clone()).or_default();
+                    entry.total += count;
+                    entry.draws += 1;
+                    entry.max = max(entry.max, *count);
+                }
+            }
+
+            for color in game.get_minimum().cubes.keys() {
+                stats.get_mut(color).unwrap().games += 1;
+            }
+        }
+
+        return stats;
+    }
+
+    fn max_draws(&self) -> Vec<(u32, &'a Draw)> {
+        return self
+            .games
+            .iter()
+            .filter_map(|game| game.max_draw().map(|draw| (game.id, draw)))
+            .collect();
+    }
+
+    fn to_json(&self, constraints: &[Constraint]) -> String {
+        let matching = self.filter(constraints);
+
+        let ids = matching
+            .iter()
+            .map(|game| game.id.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        let id_sum: u32 = matching.iter().map(|game| game.id).sum();
+
+        let stats = self
+            .color_stats()
+            .iter()
+            .map(|(color, stats)| {
+                format!(
+                    "\"{}\":{{\"total\":{},\"draws\":{},\"games\":{},\"max\":{}}}",
+                    color, stats.total, stats.draws, stats.games, stats.max
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+
+        let max_draws = self
+            .max_draws()
+            .iter()
+            .map(|(id, draw)| format!("{{\"game\":{},\"draw\":{}}}", id, draw.to_json()))
+            .collect::<Vec<String>>()
+            .join(",");
+
+        return format!(
+            "{{\"games\":[{}],\"id_sum\":{},\"color_stats\":{{{}}},\"max_draws\":[{}]}}",
+            ids, id_sum, stats, max_draws
+        );
+    }
+}
+
 fn main() {
     let file = File::open("input").unwrap();
     let lines = BufReader::new(file).lines();
@@ -133,6 +328,17 @@ fn main() {
         .map(|line| line.unwrap().parse::<Game>().unwrap())
         .collect();
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|command| command == "query") {
+        let constraints = args[1..]
+            .iter()
+            .map(|arg| arg.parse::<Constraint>().unwrap())
+            .collect::<Vec<Constraint>>();
+
+        println!("{}", GameQuery::new(&games).to_json(&constraints));
+        return;
+    }
+
     let sum_ids = games
         .iter()
         .filter(|g| g.is_possible(12, 13, 14))
@@ -151,7 +357,19 @@ fn main() {
 mod tests {
     use std::vec;
 
-    use crate::{Draw, Game};
+    use crate::{ColorStats, Comparison, Constraint, Draw, Game, GameQuery};
+
+    fn get_games() -> Vec<Game> {
+        return [
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue",
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red",
+            "Game 4: 2 yellow, 1 red; 7 yellow",
+        ]
+        .iter()
+        .map(|line| line.parse::<Game>().unwrap())
+        .collect();
+    }
 
     #[test]
     fn test_draws() {
@@ -209,4 +427,102 @@ mod tests {
             Draw::new(20, 13, 6)
         )
     }
+
+    #[test]
+    fn test_parse_dynamic_colors() {
+        let draw = "2 yellow, 1 red, 3 purple".parse::<Draw>().unwrap();
+        assert_eq!(draw.get("yellow"), 2);
+        assert_eq!(draw.get("purple"), 3);
+        assert_eq!(draw.get("red"), 1);
+        assert_eq!(draw.get("blue"), 0);
+        assert_eq!(draw.total(), 6);
+    }
+
+    #[test]
+    fn test_parse_constraint() {
+        assert_eq!(
+            "red<=12".parse::<Constraint>().unwrap(),
+            Constraint::new("red", Comparison::AtMost, 12)
+        );
+        assert_eq!(
+            "yellow >= 3".parse::<Constraint>().unwrap(),
+            Constraint::new("yellow", Comparison::AtLeast, 3)
+        );
+        assert_eq!(
+            "blue=0".parse::<Constraint>().unwrap(),
+            Constraint::new("blue", Comparison::Exactly, 0)
+        );
+
+        assert!("red<12".parse::<Constraint>().is_err());
+        assert!("<=12".parse::<Constraint>().is_err());
+        assert!("red<=".parse::<Constraint>().is_err());
+    }
+
+    #[test]
+    fn test_query_filter() {
+        let games = get_games();
+        let query = GameQuery::new(&games);
+
+        let possible = vec![
+            Constraint::new("red", Comparison::AtMost, 12),
+            Constraint::new("green", Comparison::AtMost, 13),
+            Constraint::new("blue", Comparison::AtMost, 14),
+        ];
+        let ids: Vec<u32> = query.filter(&possible).iter().map(|game| game.id).collect();
+        assert_eq!(ids, vec![1, 2, 4]);
+
+        let yellow = vec![Constraint::new("yellow", Comparison::AtLeast, 7)];
+        let ids: Vec<u32> = query.filter(&yellow).iter().map(|game| game.id).collect();
+        assert_eq!(ids, vec![4]);
+
+        let no_blue = vec![Constraint::new("blue", Comparison::Exactly, 0)];
+        let ids: Vec<u32> = query.filter(&no_blue).iter().map(|game| game.id).collect();
+        assert_eq!(ids, vec![4]);
+    }
+
+    #[test]
+    fn test_query_color_stats() {
+        let games = get_games();
+        let stats = GameQuery::new(&games).color_stats();
+
+        assert_eq!(
+            stats["red"],
+            ColorStats {
+                total: 32,
+                draws: 7,
+                games: 4,
+                max: 20
+            }
+        );
+        assert_eq!(
+            stats["yellow"],
+            ColorStats {
+                total: 9,
+                draws: 2,
+                games: 1,
+                max: 7
+            }
+        );
+    }
+
+    #[test]
+    fn test_query_max_draws() {
+        let games = get_games();
+        let max_draws = GameQuery::new(&games).max_draws();
+
+        assert_eq!(max_draws[0], (1, &Draw::new(1, 2, 6)));
+        assert_eq!(max_draws[2], (3, &Draw::new(20, 8, 6)));
+        assert_eq!(max_draws[3].1.get("yellow"), 7);
+    }
+
+    #[test]
+    fn test_query_json() {
+        let games = get_games();
+        let json =
+            GameQuery::new(&games).to_json(&[Constraint::new("yellow", Comparison::AtLeast, 1)]);
+
+        assert!(json.starts_with("{\"games\":[4],\"id_sum\":4,\"color_stats\":{\"blue\":"));
+        assert!(json.contains("\"yellow\":{\"total\":9,\"draws\":2,\"games\":1,\"max\":7}"));
+        assert!(json.ends_with("{\"game\":4,\"draw\":{\"yellow\":7}}]}"));
+    }
 }
@@ -1,16 +1,18 @@
+#![allow(clippy::needless_return)]
+
 use std::{
     collections::HashMap,
+    env,
     fs::File,
     io::{BufRead, BufReader},
-    usize,
+    process,
 };
 
 fn get_adjacent_indexes(i: usize, j: usize, n: usize, m: usize) -> Vec<(usize, usize)> {
     let delta: [i32; 3] = [-1, 0, 1];
     let delta = delta
         .iter()
-        .map(|di| delta.map(|dj| (di, dj)))
-        .flatten()
+        .flat_map(|di| delta.map(|dj| (di, dj)))
         .filter(|(di, dj)| **di != 0 || *dj != 0);
 
     return delta
@@ -30,79 +32,177 @@ fn get_adjacent_indexes(i: usize, j: usize, n: usize, m: usize) -> Vec<(usize, u
         .collect::<Vec<(usize, usize)>>();
 }
 
-fn find_nums_adjacent_symbols(schematic: &Vec<Vec<char>>) -> Vec<u32> {
-    let mut nums: Vec<u32> = Vec::new();
-    for i in 0..schematic.len() {
-        let mut num = 0;
-        let mut adjacent = false;
-        for j in 0..schematic[i].len() {
-            if schematic[i][j].is_numeric() {
-                num = num * 10 + schematic[i][j].to_digit(10).unwrap();
-
-                for (ni, nj) in get_adjacent_indexes(i, j, schematic.len(), schematic[i].len()) {
-                    if schematic[ni][nj] != '.' && !schematic[ni][nj].is_alphanumeric() {
-                        adjacent = true;
-                    }
-                }
-            } else {
-                if adjacent {
-                    nums.push(num);
-                }
-                adjacent = false;
-                num = 0;
-            }
-        }
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Span {
+    row: usize,
+    start: usize,
+    end: usize,
+}
 
-        if adjacent {
-            nums.push(num);
-        }
+impl Span {
+    fn positions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        return (self.start..self.end).map(|j| (self.row, j));
     }
+}
 
-    return nums;
+#[derive(Debug, PartialEq, Eq)]
+struct Number {
+    value: u32,
+    span: Span,
 }
 
-fn find_nums_gear_ratios(schematic: &Vec<Vec<char>>) -> Vec<u32> {
-    let mut potential_gears: HashMap<i32, Vec<u32>> = HashMap::new();
+#[derive(Debug, PartialEq, Eq)]
+struct Symbol {
+    kind: char,
+    pos: (usize, usize),
+}
 
-    for i in 0..schematic.len() {
-        let mut num = 0;
-        let mut adjacent: i32 = -1;
-        for j in 0..schematic[i].len() {
-            if schematic[i][j].is_numeric() {
-                num = num * 10 + schematic[i][j].to_digit(10).unwrap();
+fn is_symbol(ch: char) -> bool {
+    return ch != '.' && !ch.is_alphanumeric();
+}
 
-                for (ni, nj) in get_adjacent_indexes(i, j, schematic.len(), schematic[i].len()) {
-                    if schematic[ni][nj] == '*' {
-                        adjacent = i32::try_from(ni * schematic.len() + nj).unwrap();
-                    }
+#[derive(Debug)]
+struct Schematic {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+    number_symbols: Vec<Vec<usize>>,
+    symbol_numbers: Vec<Vec<usize>>,
+}
+
+impl Schematic {
+    fn parse(grid: &[Vec<char>]) -> Schematic {
+        let mut numbers: Vec<Number> = Vec::new();
+        let mut symbols: Vec<Symbol> = Vec::new();
+        let mut symbol_at: HashMap<(usize, usize), usize> = HashMap::new();
+
+        for (i, row) in grid.iter().enumerate() {
+            let mut start: Option<usize> = None;
+            let mut value = 0;
+            for (j, ch) in row.iter().enumerate() {
+                if let Some(digit) = ch.to_digit(10) {
+                    start.get_or_insert(j);
+                    value = value * 10 + digit;
+                    continue;
+                }
+
+                if let Some(start) = start.take() {
+                    let span = Span {
+                        row: i,
+                        start,
+                        end: j,
+                    };
+                    numbers.push(Number { value, span });
+                    value = 0;
                 }
-            } else {
-                if adjacent >= 0 {
-                    potential_gears
-                        .entry(adjacent)
-                        .or_insert(Vec::new())
-                        .push(num);
+
+                if is_symbol(*ch) {
+                    symbol_at.insert((i, j), symbols.len());
+                    symbols.push(Symbol {
+                        kind: *ch,
+                        pos: (i, j),
+                    });
                 }
-                adjacent = -1;
-                num = 0;
+            }
+
+            if let Some(start) = start {
+                let span = Span {
+                    row: i,
+                    start,
+                    end: row.len(),
+                };
+                numbers.push(Number { value, span });
             }
         }
 
-        if adjacent >= 0 {
-            potential_gears
-                .entry(adjacent)
-                .or_insert(Vec::new())
-                .push(num);
+        let mut number_symbols: Vec<Vec<usize>> = vec![Vec::new(); numbers.len()];
+        let mut symbol_numbers: Vec<Vec<usize>> = vec![Vec::new(); symbols.len()];
+        for (number, neighbours) in numbers.iter().zip(number_symbols.iter_mut()) {
+            for (i, j) in number.span.positions() {
+                for pos in get_adjacent_indexes(i, j, grid.len(), grid[i].len()) {
+                    if let Some(symbol) = symbol_at.get(&pos) {
+                        if !neighbours.contains(symbol) {
+                            neighbours.push(*symbol);
+                        }
+                    }
+                }
+            }
+        }
+
+        for (number, neighbours) in number_symbols.iter().enumerate() {
+            for symbol in neighbours {
+                symbol_numbers[*symbol].push(number);
+            }
         }
+
+        return Schematic {
+            numbers,
+            symbols,
+            number_symbols,
+            symbol_numbers,
+        };
+    }
+
+    fn symbols_of(&self, number: usize) -> impl Iterator<Item = &Symbol> {
+        return self.number_symbols[number]
+            .iter()
+            .map(|symbol| &self.symbols[*symbol]);
+    }
+
+    fn numbers_of(&self, symbol: usize) -> impl Iterator<Item = &Number> {
+        return self.symbol_numbers[symbol]
+            .iter()
+            .map(|number| &self.numbers[*number]);
     }
 
-    return potential_gears
-        .into_iter()
-        .filter(|(_, nums)| nums.len() == 2)
-        .map(|(_, nums)| nums[0] * nums[1])
+    fn part_numbers(&self) -> Vec<&Number> {
+        return self
+            .numbers
+            .iter()
+            .zip(self.number_symbols.iter())
+            .filter(|(_, symbols)| !symbols.is_empty())
+            .map(|(number, _)| number)
+            .collect();
+    }
+
+    fn numbers_touching(&self, kind: char) -> Vec<&Number> {
+        return (0..self.numbers.len())
+            .filter(|number| self.symbols_of(*number).any(|symbol| symbol.kind == kind))
+            .map(|number| &self.numbers[number])
+            .collect();
+    }
+
+    fn symbols_with_neighbours(&self, k: usize) -> Vec<&Symbol> {
+        return self
+            .symbols
+            .iter()
+            .zip(self.symbol_numbers.iter())
+            .filter(|(_, numbers)| numbers.len() == k)
+            .map(|(symbol, _)| symbol)
+            .collect();
+    }
+
+    fn gear_ratios(&self) -> Vec<u32> {
+        return (0..self.symbols.len())
+            .filter(|symbol| {
+                self.symbols[*symbol].kind == '*' && self.symbol_numbers[*symbol].len() == 2
+            })
+            .map(|symbol| self.numbers_of(symbol).map(|number| number.value).product())
+            .collect();
+    }
+}
+
+fn find_nums_adjacent_symbols(schematic: &[Vec<char>]) -> Vec<u32> {
+    return Schematic::parse(schematic)
+        .part_numbers()
+        .iter()
+        .map(|number| number.value)
         .collect();
 }
 
+fn find_nums_gear_ratios(schematic: &[Vec<char>]) -> Vec<u32> {
+    return Schematic::parse(schematic).gear_ratios();
+}
+
 fn parse_schematic(buf: impl BufRead) -> Vec<Vec<char>> {
     let mut schematic: Vec<Vec<char>> = Vec::new();
     for line in buf.lines() {
@@ -111,11 +211,64 @@ fn parse_schematic(buf: impl BufRead) -> Vec<Vec<char>> {
 
     return schematic;
 }
+
+#[derive(Debug, PartialEq, Eq)]
+enum Query {
+    // Numbers next to a symbol of this kind.
+    Touching(char),
+    // Symbols next to exactly this many numbers.
+    Neighbours(usize),
+}
+
+fn parse_query(args: &[String]) -> Option<Query> {
+    if args.len() != 2 {
+        return None;
+    }
+
+    return match args[0].as_str() {
+        "touching" => {
+            let mut chars = args[1].chars();
+            match (chars.next(), chars.next()) {
+                (Some(kind), None) if is_symbol(kind) => Some(Query::Touching(kind)),
+                _ => None,
+            }
+        }
+        "neighbours" => Some(Query::Neighbours(args[1].parse().ok()?)),
+        _ => None,
+    };
+}
+
 fn main() {
     let file = File::open("input").unwrap();
 
     let schematic = parse_schematic(BufReader::new(file));
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        let Some(query) = parse_query(&args) else {
+            eprintln!("usage: touching <symbol> | neighbours <count>");
+            process::exit(1);
+        };
+
+        let schematic = Schematic::parse(&schematic);
+        match query {
+            Query::Touching(kind) => {
+                for number in schematic.numbers_touching(kind) {
+                    println!(
+                        "{} at {},{}",
+                        number.value, number.span.row, number.span.start
+                    );
+                }
+            }
+            Query::Neighbours(k) => {
+                for symbol in schematic.symbols_with_neighbours(k) {
+                    println!("{} at {},{}", symbol.kind, symbol.pos.0, symbol.pos.1);
+                }
+            }
+        }
+        return;
+    }
+
     let nums = find_nums_adjacent_symbols(&schematic);
     println!("{}", nums.iter().sum::<u32>());
 
@@ -127,7 +280,10 @@ fn main() {
 mod tests {
     use std::io::BufReader;
 
-    use crate::{find_nums_adjacent_symbols, find_nums_gear_ratios, parse_schematic};
+    use crate::{
+        find_nums_adjacent_symbols, find_nums_gear_ratios, parse_query, parse_schematic, Number,
+        Query, Schematic, Span, Symbol,
+    };
 
     const SCHEMATIC: &str = "467..114..
 ...*......
@@ -167,4 +323,88 @@ mod tests {
         expected_gear_ratios.sort();
         assert_eq!(gear_ratios, expected_gear_ratios);
     }
+
+    #[test]
+    fn test_schematic_entities() {
+        let schematic = Schematic::parse(&parse_schematic(BufReader::new(SCHEMATIC.as_bytes())));
+
+        assert_eq!(schematic.numbers.len(), 13);
+        assert_eq!(
+            schematic.numbers[1],
+            Number {
+                value: 114,
+                span: Span {
+                    row: 0,
+                    start: 5,
+                    end: 8
+                }
+            }
+        );
+        assert_eq!(
+            schematic.numbers[8],
+            Number {
+                value: 7556,
+                span: Span {
+                    row: 7,
+                    start: 6,
+                    end: 10
+                }
+            }
+        );
+        assert_eq!(schematic.symbols.len(), 6);
+        assert_eq!(
+            schematic.symbols[2],
+            Symbol {
+                kind: '*',
+                pos: (4, 3)
+            }
+        );
+
+        let symbols: Vec<char> = schematic.symbols_of(9).map(|symbol| symbol.kind).collect();
+        assert_eq!(symbols, vec!['$']);
+    }
+
+    #[test]
+    fn test_schematic_queries() {
+        let schematic = Schematic::parse(&parse_schematic(BufReader::new(SCHEMATIC.as_bytes())));
+
+        let touching_hash: Vec<u32> = schematic
+            .numbers_touching('#')
+            .iter()
+            .map(|number| number.value)
+            .collect();
+        assert_eq!(touching_hash, vec![633]);
+
+        let lonely: Vec<(usize, usize)> = schematic
+            .symbols_with_neighbours(1)
+            .iter()
+            .map(|symbol| symbol.pos)
+            .collect();
+        assert_eq!(lonely, vec![(3, 6), (4, 3), (5, 5)]);
+
+        let pairs: Vec<char> = schematic
+            .symbols_with_neighbours(2)
+            .iter()
+            .map(|symbol| symbol.kind)
+            .collect();
+        assert_eq!(pairs, vec!['*', '$', '*']);
+
+        assert_eq!(schematic.symbols_with_neighbours(0), Vec::<&Symbol>::new());
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let args = |s: &str| -> Vec<String> { s.split(' ').map(String::from).collect() };
+
+        assert_eq!(parse_query(&args("touching #")), Some(Query::Touching('#')));
+        assert_eq!(
+            parse_query(&args("neighbours 2")),
+            Some(Query::Neighbours(2))
+        );
+        assert_eq!(parse_query(&args("touching .")), None);
+        assert_eq!(parse_query(&args("touching ##")), None);
+        assert_eq!(parse_query(&args("neighbours -1")), None);
+        assert_eq!(parse_query(&args("neighbours")), None);
+        assert_eq!(parse_query(&args("gears 2")), None);
+    }
 }
@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return)]
+
 use std::{
     cmp::min,
     collections::HashSet,
@@ -5,7 +7,6 @@ use std::{
     io::{BufRead, BufReader},
     num::ParseIntError,
     str::FromStr,
-    u32,
 };
 
 struct Card {
@@ -73,26 +74,74 @@ impl FromStr for Card {
     }
 }
 
-struct CardInstances<'a> {
-    card: &'a Card,
-    count: usize,
+fn get_card_copies(cards: &[Card]) -> Vec<u128> {
+    let mut expiring: Vec<u128> = vec![0; cards.len() + 1];
+    let mut copies: Vec<u128> = Vec::with_capacity(cards.len());
+
+    let mut won = 0;
+    for (i, card) in cards.iter().enumerate() {
+        won -= expiring[i];
+        copies.push(1 + won);
+
+        let end = min(i + 1 + card.winning_count(), cards.len());
+        if i + 1 < end {
+            won += copies[i];
+            expiring[end] += copies[i];
+        }
+    }
+
+    return copies;
+}
+
+// Number of copies a single original card ends up winning, including the
+// copies won by the copies it won.
+fn get_card_contributions(cards: &[Card]) -> Vec<u128> {
+    let mut reach: Vec<u128> = vec![0; cards.len()];
+    let mut suffix: Vec<u128> = vec![0; cards.len() + 1];
+
+    for (i, card) in cards.iter().enumerate().rev() {
+        let end = min(i + 1 + card.winning_count(), cards.len());
+        reach[i] = 1 + suffix[i + 1] - suffix[end];
+        suffix[i] = suffix[i + 1] + reach[i];
+    }
+
+    return reach.iter().map(|count| count - 1).collect();
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct CardReport {
+    id: usize,
+    wins: usize,
+    copies: u128,
+    contributed: u128,
 }
 
-fn get_won_cards(cards: &Vec<Card>) -> usize {
-    let mut instances: Vec<CardInstances> = cards
+fn get_card_report(cards: &[Card]) -> Vec<CardReport> {
+    let copies = get_card_copies(cards);
+    let contributions = get_card_contributions(cards);
+
+    return cards
         .iter()
-        .map(|card| CardInstances { card, count: 1 })
+        .enumerate()
+        .map(|(i, card)| CardReport {
+            id: i + 1,
+            wins: card.winning_count(),
+            copies: copies[i],
+            contributed: contributions[i],
+        })
         .collect();
+}
 
-    let mut won_count = 0;
-    for i in 0..instances.len() {
-        won_count += instances[i].count;
-        for j in i + 1..min(i + 1 + instances[i].card.winning_count(), instances.len()) {
-            instances[j].count += instances[i].count;
-        }
-    }
+fn get_top_contributors(cards: &[Card], count: usize) -> Vec<CardReport> {
+    let mut report = get_card_report(cards);
+    report.sort_by(|a, b| b.contributed.cmp(&a.contributed).then(a.id.cmp(&b.id)));
+    report.truncate(count);
+
+    return report;
+}
 
-    return won_count;
+fn get_won_cards(cards: &[Card]) -> u128 {
+    return get_card_copies(cards).iter().sum();
 }
 
 fn parse_cards(buf: impl BufRead) -> Vec<Card> {
@@ -107,21 +156,28 @@ fn main() {
     let file = File::open("input").unwrap();
 
     let cards = parse_cards(BufReader::new(file));
-    let sum = cards
-        .iter()
-        .map(|c| c.get_points())
-        .fold(0, |sum, points| sum + points);
+    let sum = cards.iter().map(|c| c.get_points()).sum::<u32>();
 
     println!("{}", sum);
 
     println!("{}", get_won_cards(&cards));
+
+    for report in get_top_contributors(&cards, 5) {
+        println!(
+            "Card {}: {} wins, {} copies, contributed {} copies",
+            report.id, report.wins, report.copies, report.contributed
+        );
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::BufReader;
 
-    use crate::{get_won_cards, parse_cards, Card};
+    use crate::{
+        get_card_contributions, get_card_copies, get_card_report, get_top_contributors,
+        get_won_cards, parse_cards, Card, CardReport,
+    };
 
     const CARD: &str = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53";
     const CARDS: &str = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
@@ -209,4 +265,61 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
 
         assert_eq!(get_won_cards(&cards), 30);
     }
+
+    #[test]
+    fn test_get_card_copies() {
+        let cards = parse_cards(BufReader::new(CARDS.as_bytes()));
+
+        assert_eq!(get_card_copies(&cards), vec![1, 2, 4, 8, 14, 1]);
+    }
+
+    #[test]
+    fn test_get_card_contributions() {
+        let cards = parse_cards(BufReader::new(CARDS.as_bytes()));
+        let contributions = get_card_contributions(&cards);
+
+        assert_eq!(contributions, vec![14, 6, 3, 1, 0, 0]);
+        assert_eq!(
+            contributions.iter().map(|c| c + 1).sum::<u128>(),
+            get_won_cards(&cards)
+        );
+    }
+
+    #[test]
+    fn test_get_card_copies_large() {
+        let cards: Vec<Card> = (0..100)
+            .map(|i| {
+                if i < 90 {
+                    Card::new(vec![1, 2, 3, 4, 5], vec![1, 2, 3, 4, 5])
+                } else {
+                    Card::new(vec![1], vec![2])
+                }
+            })
+            .collect();
+
+        let copies = get_card_copies(&cards);
+        assert!(copies.iter().any(|count| *count > u64::MAX as u128));
+        assert_eq!(get_won_cards(&cards), copies.iter().sum::<u128>());
+    }
+
+    #[test]
+    fn test_get_card_report() {
+        let cards = parse_cards(BufReader::new(CARDS.as_bytes()));
+
+        assert_eq!(
+            get_card_report(&cards)[2],
+            CardReport {
+                id: 3,
+                wins: 2,
+                copies: 4,
+                contributed: 3
+            }
+        );
+
+        let top: Vec<usize> = get_top_contributors(&cards, 3)
+            .iter()
+            .map(|report| report.id)
+            .collect();
+        assert_eq!(top, vec![1, 2, 3]);
+    }
 }
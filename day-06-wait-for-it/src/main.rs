@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return)]
+
 use std::fs;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -7,10 +9,14 @@ struct Race {
 }
 
 impl Race {
-    fn winning_ways(&self) -> u64 {
-        let time = self.time as i128;
-        let distance = self.distance as i128;
-        let determinant: i128 = time * time - 4 * distance;
+    fn beats(&self, hold: u64) -> bool {
+        let hold = hold as u128;
+        return hold * (self.time as u128 - hold) >= self.distance as u128;
+    }
+
+    fn winning_range(&self) -> Option<(u64, u64)> {
+        let time = self.time as u128;
+        let distance = self.distance as u128;
 
         //because the equation is
         // x^2 - time * x + distance <= 0
@@ -19,14 +25,29 @@ impl Race {
         // doesn't intersect the x-axis
         // meaning that the function value
         // is always greater than 0
-        if determinant < 0 {
-            return 0;
+        if time * time < 4 * distance {
+            return None;
+        }
+
+        // The integer square root is exact, so the estimate is at most one
+        // step away from the real bound in either direction.
+        let root = (time * time - 4 * distance).isqrt() as u64;
+        let mut lower_bound = (self.time - root) / 2;
+        while lower_bound > 0 && self.beats(lower_bound - 1) {
+            lower_bound -= 1;
+        }
+        while !self.beats(lower_bound) {
+            lower_bound += 1;
         }
 
-        let lower_bound = (((self.time as f64) - (determinant as f64).sqrt()) / 2.0).ceil() as u64;
-        let upper_bound = (((self.time as f64) + (determinant as f64).sqrt()) / 2.0).floor() as u64;
+        return Some((lower_bound, self.time - lower_bound));
+    }
 
-        return upper_bound - lower_bound + 1;
+    fn winning_ways(&self) -> u128 {
+        return match self.winning_range() {
+            Some((lower_bound, upper_bound)) => (upper_bound - lower_bound) as u128 + 1,
+            None => 0,
+        };
     }
 }
 
@@ -100,7 +121,7 @@ fn main() {
 
     println!(
         "{}",
-        races.iter().map(|race| race.winning_ways()).sum::<u64>()
+        races.iter().map(|race| race.winning_ways()).sum::<u128>()
     );
 
     let race = parse_race(&input).unwrap();
@@ -170,6 +191,70 @@ Distance:  9  40  200";
         );
     }
 
+    fn brute_force_range(race: &Race) -> Option<(u64, u64)> {
+        let winning: Vec<u64> = (0..=race.time).filter(|hold| race.beats(*hold)).collect();
+
+        return winning.first().map(|lo| (*lo, *winning.last().unwrap()));
+    }
+
+    #[test]
+    fn test_winning_range_against_brute_force() {
+        let mut state: u64 = 0x2545F4914F6CDD1D;
+        let mut next = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+
+        for _ in 0..2000 {
+            let time = next(500);
+            let distance = next(time * time / 4 + 20);
+            let race = Race { time, distance };
+
+            assert_eq!(race.winning_range(), brute_force_range(&race), "{:?}", race);
+        }
+    }
+
+    #[test]
+    fn test_winning_range_large() {
+        let mut state: u64 = 0x9E3779B97F4A7C15;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..2000 {
+            let time = next();
+            let half = time / 2;
+            let race = Race {
+                time,
+                distance: ((half as u128 * (time - half) as u128) as u64).min(next()),
+            };
+
+            let (lo, hi) = race.winning_range().unwrap();
+            assert_eq!(hi, time - lo);
+            assert!(race.beats(lo), "{:?}", race);
+            assert!(lo == 0 || !race.beats(lo - 1), "{:?}", race);
+        }
+
+        let race = Race {
+            time: u64::MAX,
+            distance: 0,
+        };
+        assert_eq!(race.winning_range(), Some((0, u64::MAX)));
+        assert_eq!(race.winning_ways(), u64::MAX as u128 + 1);
+
+        // The f64 version counted holds 0 through 16 as winning here
+        let race = Race {
+            time: 1 << 60,
+            distance: u64::MAX,
+        };
+        assert_eq!(race.winning_range(), Some((17, (1 << 60) - 17)));
+    }
+
     #[test]
     fn test_parse_race() {
         let race = parse_race(RACES);
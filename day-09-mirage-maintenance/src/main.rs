@@ -1,48 +1,125 @@
+#![allow(clippy::needless_return)]
+
 use std::{
     fs::File,
     io::{BufRead, BufReader},
 };
 
-fn predict_next(sequence: Vec<i64>) -> (i64, i64) {
-    if sequence.iter().all(|el| *el == 0) {
-        return (0, 0);
+#[derive(Debug, PartialEq, Eq)]
+enum SequenceError {
+    Empty,
+    NoZeroRow,
+    Overflow,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Sequence {
+    // Newton forward difference coefficients, the first element of every
+    // row of the difference table down to the last non zero row.
+    coefficients: Vec<i128>,
+}
+
+impl Sequence {
+    fn new(values: &[i64]) -> Result<Sequence, SequenceError> {
+        if values.is_empty() {
+            return Err(SequenceError::Empty);
+        }
+
+        let mut row: Vec<i128> = values.iter().map(|value| *value as i128).collect();
+        let mut coefficients = Vec::new();
+
+        while !row.iter().all(|value| *value == 0) {
+            if row.len() == 1 {
+                return Err(SequenceError::NoZeroRow);
+            }
+
+            coefficients.push(row[0]);
+            row = row
+                .windows(2)
+                .map(|pair| pair[1].checked_sub(pair[0]).ok_or(SequenceError::Overflow))
+                .collect::<Result<_, _>>()?;
+        }
+
+        return Ok(Sequence { coefficients });
     }
 
-    let mut diffs = Vec::new();
-    for i in 1..sequence.len() {
-        diffs.push(sequence[i] - sequence[i - 1]);
+    fn degree(&self) -> usize {
+        return self.coefficients.len().saturating_sub(1);
     }
 
-    let next_diff = predict_next(diffs);
+    fn value_at(&self, index: i128) -> Result<i128, SequenceError> {
+        let mut value: i128 = 0;
+        let mut binomial: i128 = 1;
 
-    return (
-        sequence.first().unwrap() - next_diff.0,
-        sequence.last().unwrap() + next_diff.1,
-    );
+        for (k, coefficient) in self.coefficients.iter().enumerate() {
+            if k > 0 {
+                // binomial(index, k) = binomial(index, k - 1) * (index - k + 1) / k,
+                // the division is always exact.
+                binomial = binomial
+                    .checked_mul(index - k as i128 + 1)
+                    .ok_or(SequenceError::Overflow)?
+                    / k as i128;
+            }
+
+            let term = coefficient
+                .checked_mul(binomial)
+                .ok_or(SequenceError::Overflow)?;
+            value = value.checked_add(term).ok_or(SequenceError::Overflow)?;
+        }
+
+        return Ok(value);
+    }
 }
 
 fn main() {
     let file = File::open("input").unwrap();
 
-    let sum: (i64, i64) = BufReader::new(file)
+    let (sum, degree): ((i128, i128), usize) = BufReader::new(file)
         .lines()
         .map(|line| {
             line.unwrap()
-                .split(" ")
+                .split(' ')
                 .map(|num| num.parse::<i64>().unwrap())
                 .collect::<Vec<i64>>()
         })
-        .map(|sequence| predict_next(sequence))
-        .fold((0, 0), |sums, predicted| {
-            (sums.0 + predicted.0, sums.1 + predicted.1)
+        .map(|values| {
+            let sequence = Sequence::new(&values).unwrap();
+            (
+                sequence.value_at(-1).unwrap(),
+                sequence.value_at(values.len() as i128).unwrap(),
+                sequence.degree(),
+            )
+        })
+        .fold(((0, 0), 0), |(sums, degree), predicted| {
+            (
+                (sums.0 + predicted.0, sums.1 + predicted.1),
+                degree.max(predicted.2),
+            )
         });
 
     println!("Previous sum: {}, Next sum: {}", sum.0, sum.1);
+    println!("Highest degree: {}", degree);
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::predict_next;
+    use crate::{Sequence, SequenceError};
+
+    // The original recursive extrapolation, kept as a reference for Sequence.
+    fn predict_next(sequence: Vec<i64>) -> (i64, i64) {
+        if sequence.iter().all(|el| *el == 0) {
+            return (0, 0);
+        }
+
+        let diffs: Vec<i64> = sequence.windows(2).map(|pair| pair[1] - pair[0]).collect();
+
+        let next_diff = predict_next(diffs);
+
+        return (
+            sequence.first().unwrap() - next_diff.0,
+            sequence.last().unwrap() + next_diff.1,
+        );
+    }
 
     #[test]
     fn test_predict_next() {
@@ -50,4 +127,65 @@ mod tests {
         assert_eq!(predict_next(vec![1, 3, 6, 10, 15, 21]), (0, 28));
         assert_eq!(predict_next(vec![10, 13, 16, 21, 30, 45]), (5, 68));
     }
+
+    #[test]
+    fn test_sequence_degree() {
+        assert_eq!(Sequence::new(&[0, 3, 6, 9, 12, 15]).unwrap().degree(), 1);
+        assert_eq!(Sequence::new(&[1, 3, 6, 10, 15, 21]).unwrap().degree(), 2);
+        assert_eq!(
+            Sequence::new(&[10, 13, 16, 21, 30, 45]).unwrap().degree(),
+            3
+        );
+        assert_eq!(Sequence::new(&[7, 7, 7]).unwrap().degree(), 0);
+        assert_eq!(Sequence::new(&[0, 0]).unwrap().degree(), 0);
+    }
+
+    #[test]
+    fn test_sequence_errors() {
+        assert_eq!(Sequence::new(&[]), Err(SequenceError::Empty));
+        assert_eq!(Sequence::new(&[1, 2, 4, 8]), Err(SequenceError::NoZeroRow));
+        assert_eq!(Sequence::new(&[5]), Err(SequenceError::NoZeroRow));
+
+        // the differences double on every row until they leave i128
+        assert_eq!(
+            Sequence::new(&[i64::MAX, i64::MIN].repeat(40)),
+            Err(SequenceError::Overflow)
+        );
+
+        let sequence = Sequence::new(&[0, 1, 4, 9]).unwrap();
+        assert_eq!(sequence.value_at(1 << 70), Err(SequenceError::Overflow));
+    }
+
+    #[test]
+    fn test_sequence_matches_predict_next() {
+        for values in [
+            vec![0, 3, 6, 9, 12, 15],
+            vec![1, 3, 6, 10, 15, 21],
+            vec![10, 13, 16, 21, 30, 45],
+            vec![-7, -12, -15, -4, 52, 210, 556, 1204, 2291],
+        ] {
+            let sequence = Sequence::new(&values).unwrap();
+            let (previous, next) = predict_next(values.clone());
+
+            assert_eq!(sequence.value_at(-1), Ok(previous as i128));
+            assert_eq!(sequence.value_at(values.len() as i128), Ok(next as i128));
+        }
+    }
+
+    #[test]
+    fn test_sequence_value_at() {
+        let sequence = Sequence::new(&[1, 3, 6, 10, 15, 21]).unwrap();
+
+        // triangular numbers, value at index n is (n + 1)(n + 2) / 2
+        for n in [0, 5, -1, -2, -10, 1000] {
+            assert_eq!(sequence.value_at(n), Ok((n + 1) * (n + 2) / 2));
+        }
+
+        let n: i128 = 1_000_000_000_000_000;
+        assert_eq!(sequence.value_at(n), Ok((n + 1) * (n + 2) / 2));
+
+        let cubes = Sequence::new(&[0, 1, 8, 27, 64]).unwrap();
+        assert_eq!(cubes.value_at(-3), Ok(-27));
+        assert_eq!(cubes.value_at(1_000_000), Ok(1_000_000_000_000_000_000));
+    }
 }
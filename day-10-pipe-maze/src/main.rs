@@ -1,12 +1,14 @@
-use std::{collections::VecDeque, fs};
+#![allow(clippy::needless_return)]
 
-#[derive(Debug, PartialEq, Eq)]
+use std::{collections::VecDeque, env, fs};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 struct Cell {
     i: usize,
     j: usize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum Pipe {
     Vertical,
     Horizontal,
@@ -29,6 +31,43 @@ impl Pipe {
             Pipe::Ground => (Cell { i, j }, Cell { i, j }),
         };
     }
+
+    fn get_connections(&self, cell: &Cell, height: usize, width: usize) -> Option<(Cell, Cell)> {
+        let (first, second) = match self {
+            Pipe::Vertical => ((1, 0), (-1, 0)),
+            Pipe::Horizontal => ((0, 1), (0, -1)),
+            Pipe::NorthEast => ((-1, 0), (0, 1)),
+            Pipe::NorthWest => ((-1, 0), (0, -1)),
+            Pipe::SouthWest => ((1, 0), (0, -1)),
+            Pipe::SouthEast => ((1, 0), (0, 1)),
+            Pipe::Ground => return None,
+        };
+
+        return Some((
+            offset(cell, first, height, width)?,
+            offset(cell, second, height, width)?,
+        ));
+    }
+}
+
+fn offset(cell: &Cell, diff: (i32, i32), height: usize, width: usize) -> Option<Cell> {
+    let i = cell.i as i32 + diff.0;
+    let j = cell.j as i32 + diff.1;
+    if i < 0 || i >= height as i32 || j < 0 || j >= width as i32 {
+        return None;
+    }
+
+    return Some(Cell {
+        i: i as usize,
+        j: j as usize,
+    });
+}
+
+fn connects_to(pipes: &[Vec<Pipe>], from: &Cell, to: &Cell) -> bool {
+    return match pipes[from.i][from.j].get_connections(from, pipes.len(), pipes[0].len()) {
+        Some((first, second)) => first == *to || second == *to,
+        None => false,
+    };
 }
 
 fn parse_pipe(ch: char) -> Pipe {
@@ -43,28 +82,17 @@ fn parse_pipe(ch: char) -> Pipe {
     };
 }
 
-fn get_next_from_start(pipes: &Vec<Vec<Pipe>>, start: &Cell) -> VecDeque<Cell> {
-    let mut q = VecDeque::new();
+fn get_next_from_start(pipes: &[Vec<Pipe>], start: &Cell) -> Vec<Cell> {
     let diffs: [(i32, i32); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
-    for diff in diffs {
-        let i = start.i as i32 + diff.0;
-        let j = start.j as i32 + diff.1;
-        if i >= 0 && i < pipes.len() as i32 && j >= 0 && j < pipes[0].len() as i32 {
-            let i = i as usize;
-            let j = j as usize;
-            let next = pipes[i][j].get_next(i, j);
-            if (next.0.i == start.i && next.0.j == start.j)
-                || (next.1.i == start.i && next.1.j == start.j)
-            {
-                q.push_back(Cell { i, j });
-            }
-        }
-    }
 
-    return q;
+    return diffs
+        .iter()
+        .filter_map(|diff| offset(start, *diff, pipes.len(), pipes[0].len()))
+        .filter(|next| connects_to(pipes, next, start))
+        .collect();
 }
 
-fn determine_start(start: &Cell, next: &VecDeque<Cell>) -> Pipe {
+fn determine_start(start: &Cell, next: &[Cell]) -> Pipe {
     if next[0].i.abs_diff(next[1].i) == 2 {
         return Pipe::Vertical;
     }
@@ -95,12 +123,49 @@ fn determine_start(start: &Cell, next: &VecDeque<Cell>) -> Pipe {
     return Pipe::SouthEast;
 }
 
-fn bfs(pipes: &mut Vec<Vec<Pipe>>, start: &Cell) -> (usize, Vec<Vec<bool>>) {
+#[derive(Debug, PartialEq, Eq)]
+enum StartError {
+    NoLoop,
+    Ambiguous(usize),
+}
+
+// Tries every pair of neighbours that connect to the start and keeps the
+// pipe only if exactly one of them closes a loop.
+fn resolve_start(pipes: &mut [Vec<Pipe>], start: &Cell) -> Result<Pipe, StartError> {
+    let next = get_next_from_start(pipes, start);
+    let original = pipes[start.i][start.j];
+
+    let mut closing: Vec<Pipe> = Vec::new();
+    for first in 0..next.len() {
+        for second in first + 1..next.len() {
+            let pipe = determine_start(start, &[next[first].clone(), next[second].clone()]);
+            pipes[start.i][start.j] = pipe;
+            if walk_loop(pipes, start).is_some() {
+                closing.push(pipe);
+            }
+        }
+    }
+
+    pipes[start.i][start.j] = original;
+    return match closing[..] {
+        [pipe] => {
+            pipes[start.i][start.j] = pipe;
+            Ok(pipe)
+        }
+        [] => Err(StartError::NoLoop),
+        _ => Err(StartError::Ambiguous(closing.len())),
+    };
+}
+
+fn bfs(pipes: &[Vec<Pipe>], start: &Cell) -> (usize, Vec<Vec<bool>>) {
     let mut level = 1;
-    let mut q: VecDeque<Cell> = get_next_from_start(&pipes, start);
-    let start_pipe = determine_start(start, &q);
-    pipes[start.i][start.j] = start_pipe;
-    println!("{:?}", pipes[start.i][start.j]);
+    let mut q: VecDeque<Cell> = VecDeque::new();
+    if let Some((first, second)) =
+        pipes[start.i][start.j].get_connections(start, pipes.len(), pipes[0].len())
+    {
+        q.push_back(first);
+        q.push_back(second);
+    }
 
     let mut visited = pipes
         .iter()
@@ -128,24 +193,135 @@ fn bfs(pipes: &mut Vec<Vec<Pipe>>, start: &Cell) -> (usize, Vec<Vec<bool>>) {
     return (level, visited);
 }
 
-fn get_inside_surfice(pipes: &Vec<Vec<Pipe>>, is_loop: &Vec<Vec<bool>>) -> u32 {
-    let mut inside_count = 0;
+fn get_inside_tiles(pipes: &[Vec<Pipe>], is_loop: &[Vec<bool>]) -> Vec<Vec<bool>> {
+    let mut inside = vec![vec![false; pipes[0].len()]; pipes.len()];
     let mut is_inside = false;
 
-    for i in 0..pipes.len() {
-        for j in 0..pipes[0].len() {
+    for (i, row) in pipes.iter().enumerate() {
+        for (j, pipe) in row.iter().enumerate() {
             if is_loop[i][j] {
-                match pipes[i][j] {
+                match pipe {
                     Pipe::Vertical | Pipe::SouthWest | Pipe::SouthEast => is_inside = !is_inside,
                     _ => {}
                 };
-            } else if is_inside {
-                inside_count += 1;
+            } else {
+                inside[i][j] = is_inside;
             }
         }
     }
 
-    return inside_count;
+    return inside;
+}
+
+fn get_inside_surfice(pipes: &[Vec<Pipe>], is_loop: &[Vec<bool>]) -> u32 {
+    return get_inside_tiles(pipes, is_loop)
+        .iter()
+        .map(|row| row.iter().filter(|inside| **inside).count() as u32)
+        .sum();
+}
+
+fn walk_loop(pipes: &[Vec<Pipe>], start: &Cell) -> Option<Vec<Cell>> {
+    let (mut current, last) =
+        pipes[start.i][start.j].get_connections(start, pipes.len(), pipes[0].len())?;
+    let mut path = vec![start.clone()];
+    let mut previous = start.clone();
+
+    while current != *start {
+        if !connects_to(pipes, &current, &previous) {
+            return None;
+        }
+
+        let next =
+            pipes[current.i][current.j].get_connections(&current, pipes.len(), pipes[0].len())?;
+        let next = if next.0 == previous { next.1 } else { next.0 };
+
+        path.push(current.clone());
+        previous = current;
+        current = next;
+    }
+
+    if previous != last {
+        return None;
+    }
+
+    return Some(path);
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct PipeLoop {
+    cells: Vec<Cell>,
+    length: usize,
+    area: usize,
+}
+
+fn find_loops(pipes: &[Vec<Pipe>]) -> Vec<PipeLoop> {
+    let mut seen = vec![vec![false; pipes[0].len()]; pipes.len()];
+    let mut loops = Vec::new();
+
+    for i in 0..pipes.len() {
+        for j in 0..pipes[i].len() {
+            if seen[i][j] || pipes[i][j] == Pipe::Ground {
+                continue;
+            }
+
+            seen[i][j] = true;
+            if let Some(cells) = walk_loop(pipes, &Cell { i, j }) {
+                for cell in &cells {
+                    seen[cell.i][cell.j] = true;
+                }
+
+                loops.push(PipeLoop {
+                    length: cells.len(),
+                    area: get_enclosed_area(&cells),
+                    cells,
+                });
+            }
+        }
+    }
+
+    return loops;
+}
+
+// Shoelace gives the area of the polygon through the loop cell centers, then
+// Pick's theorem (A = I + B / 2 - 1) leaves the tiles strictly inside.
+fn get_enclosed_area(path: &[Cell]) -> usize {
+    let mut double_area: i64 = 0;
+    for (k, current) in path.iter().enumerate() {
+        let next = &path[(k + 1) % path.len()];
+        double_area += current.j as i64 * next.i as i64 - next.j as i64 * current.i as i64;
+    }
+
+    return (double_area.unsigned_abs() as usize + 2 - path.len()) / 2;
+}
+
+fn render_loop(pipes: &[Vec<Pipe>], is_loop: &[Vec<bool>]) -> String {
+    let inside = get_inside_tiles(pipes, is_loop);
+    let mut rendered = String::new();
+
+    for (i, row) in pipes.iter().enumerate() {
+        for (j, pipe) in row.iter().enumerate() {
+            let ch = if is_loop[i][j] {
+                match pipe {
+                    Pipe::Vertical => '│',
+                    Pipe::Horizontal => '─',
+                    Pipe::NorthEast => '└',
+                    Pipe::NorthWest => '┘',
+                    Pipe::SouthWest => '┐',
+                    Pipe::SouthEast => '┌',
+                    Pipe::Ground => '?',
+                }
+            } else if inside[i][j] {
+                'I'
+            } else {
+                'O'
+            };
+
+            rendered.push(ch);
+        }
+        rendered.push('\n');
+    }
+
+    return rendered;
 }
 
 fn parse_pipes(s: &str) -> (Vec<Vec<Pipe>>, Cell) {
@@ -172,16 +348,36 @@ fn parse_pipes(s: &str) -> (Vec<Vec<Pipe>>, Cell) {
 fn main() {
     let input = fs::read_to_string("input").unwrap();
     let (mut pipes, start) = parse_pipes(&input);
+    resolve_start(&mut pipes, &start).unwrap();
 
-    let (levels, is_loop) = bfs(&mut pipes, &start);
+    let (levels, is_loop) = bfs(&pipes, &start);
     println!("{}", levels - 1);
 
     println!("{}", get_inside_surfice(&pipes, &is_loop));
+    println!("{}", get_enclosed_area(&walk_loop(&pipes, &start).unwrap()));
+
+    if env::args().any(|arg| arg == "--render") {
+        print!("{}", render_loop(&pipes, &is_loop));
+    }
+
+    if env::args().any(|arg| arg == "--loops") {
+        for pipe_loop in find_loops(&pipes) {
+            println!(
+                "loop at ({}, {}): length {}, enclosed {}",
+                pipe_loop.cells[0].i, pipe_loop.cells[0].j, pipe_loop.length, pipe_loop.area
+            );
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Cell, Pipe};
+    use std::fs;
+
+    use crate::{
+        bfs, find_loops, get_enclosed_area, get_inside_surfice, parse_pipes, render_loop,
+        resolve_start, walk_loop, Cell, Pipe, StartError,
+    };
 
     #[test]
     fn test_get_next() {
@@ -215,4 +411,93 @@ mod tests {
         assert!(pipe.contains(&Cell { i: 3, j: 3 }));
         assert!(pipe.contains(&Cell { i: 2, j: 4 }));
     }
+
+    #[test]
+    fn test_enclosed_area_matches_scanline() {
+        for (file, expected) in [
+            ("test", 1),
+            ("test2", 1),
+            ("test3", 4),
+            ("test4", 8),
+            ("test5", 10),
+            ("input", 461),
+        ] {
+            let input = fs::read_to_string(file).unwrap();
+            let (mut pipes, start) = parse_pipes(&input);
+            resolve_start(&mut pipes, &start).unwrap();
+            let (levels, is_loop) = bfs(&pipes, &start);
+
+            let path = walk_loop(&pipes, &start).unwrap();
+            assert_eq!(path.len() / 2, levels - 1, "{}", file);
+            assert_eq!(get_enclosed_area(&path), expected, "{}", file);
+            assert_eq!(
+                get_inside_surfice(&pipes, &is_loop),
+                expected as u32,
+                "{}",
+                file
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_loop() {
+        let input = fs::read_to_string("test").unwrap();
+        let (mut pipes, start) = parse_pipes(&input);
+        resolve_start(&mut pipes, &start).unwrap();
+        let (_, is_loop) = bfs(&pipes, &start);
+
+        assert_eq!(
+            render_loop(&pipes, &is_loop),
+            "OOOOO\nO┌─┐O\nO│I│O\nO└─┘O\nOOOOO\n"
+        );
+    }
+
+    #[test]
+    fn test_resolve_start() {
+        let (mut pipes, start) = parse_pipes(".....\n.F-7.\n-S.|.\n.L-J.");
+        assert_eq!(resolve_start(&mut pipes, &start), Ok(Pipe::Vertical));
+        assert_eq!(pipes[start.i][start.j], Pipe::Vertical);
+
+        let (mut pipes, start) = parse_pipes("F-7..\n|.|..\nL-S-7\n..|.|\n..L-J");
+        assert_eq!(
+            resolve_start(&mut pipes, &start),
+            Err(StartError::Ambiguous(2))
+        );
+        assert_eq!(pipes[start.i][start.j], Pipe::Ground);
+
+        let (mut pipes, start) = parse_pipes("|..\nS-7\n...");
+        assert_eq!(resolve_start(&mut pipes, &start), Err(StartError::NoLoop));
+
+        let (mut pipes, start) = parse_pipes("S");
+        assert_eq!(resolve_start(&mut pipes, &start), Err(StartError::NoLoop));
+    }
+
+    #[test]
+    fn test_find_loops() {
+        let (mut pipes, start) = parse_pipes(".....F7\n.F-7.LJ\n.S.|...\n.L-J...");
+        resolve_start(&mut pipes, &start).unwrap();
+
+        let loops: Vec<(usize, usize, usize, usize)> = find_loops(&pipes)
+            .iter()
+            .map(|pipe_loop| {
+                (
+                    pipe_loop.cells[0].i,
+                    pipe_loop.cells[0].j,
+                    pipe_loop.length,
+                    pipe_loop.area,
+                )
+            })
+            .collect();
+        assert_eq!(loops, vec![(0, 5, 4, 0), (1, 1, 8, 1)]);
+
+        let input = fs::read_to_string("test5").unwrap();
+        let (mut pipes, start) = parse_pipes(&input);
+        resolve_start(&mut pipes, &start).unwrap();
+
+        let main_loop = walk_loop(&pipes, &start).unwrap();
+        let loops = find_loops(&pipes);
+        assert!(loops
+            .iter()
+            .any(|pipe_loop| pipe_loop.length == main_loop.len() && pipe_loop.area == 10));
+    }
 }
@@ -1,9 +1,6 @@
-use std::{
-    cmp::{max, min},
-    collections::HashSet,
-    fs,
-    io::Empty,
-};
+#![allow(clippy::needless_return)]
+
+use std::{env, fs, process};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum Tile {
@@ -11,143 +8,244 @@ enum Tile {
     Empty,
 }
 
-fn expand_rows(space: &Vec<Vec<Tile>>) -> Vec<Vec<Tile>> {
+fn find_galaxies(space: &[Vec<Tile>]) -> Vec<(usize, usize)> {
+    let mut galaxies = Vec::new();
+
+    for (i, row) in space.iter().enumerate() {
+        for (j, tile) in row.iter().enumerate() {
+            if *tile == Tile::Galaxy {
+                galaxies.push((i, j));
+            }
+        }
+    }
+
+    return galaxies;
+}
+
+fn rows_to_expand(space: &[Vec<Tile>]) -> Vec<usize> {
     let mut expanded = Vec::new();
 
-    for row in space {
-        expanded.push(row.clone());
+    for (i, row) in space.iter().enumerate() {
         if row.iter().all(|tile| *tile == Tile::Empty) {
-            expanded.push(row.clone());
+            expanded.push(i);
         }
     }
 
     return expanded;
 }
 
-fn expand_columns(space: &Vec<Vec<Tile>>) -> Vec<Vec<Tile>> {
-    let mut expanded = vec![Vec::<Tile>::new(); space.len()];
+fn columns_to_expand(space: &[Vec<Tile>]) -> Vec<usize> {
+    let mut expanded = Vec::new();
 
     for j in 0..space[0].len() {
-        let mut to_expand = true;
-        for i in 0..space.len() {
-            if space[i][j] == Tile::Galaxy {
-                to_expand = false;
-                break;
-            }
-        }
+        let to_expand = space.iter().all(|row| row[j] == Tile::Empty);
 
-        for i in 0..space.len() {
-            expanded[i].push(space[i][j]);
-            if to_expand {
-                expanded[i].push(space[i][j]);
-            }
+        if to_expand {
+            expanded.push(j);
         }
     }
 
     return expanded;
 }
 
-fn find_galaxies(space: &Vec<Vec<Tile>>) -> Vec<(usize, usize)> {
-    let mut galaxies = Vec::new();
+// How many lines every empty line becomes. A factor of zero would remove
+// lines instead, so it is rejected when the factor is created.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct ExpandFactor(u128);
 
-    for i in 0..space.len() {
-        for j in 0..space[i].len() {
-            if space[i][j] == Tile::Galaxy {
-                galaxies.push((i, j));
-            }
+#[derive(Debug, PartialEq, Eq)]
+struct InvalidExpandFactor;
+
+impl ExpandFactor {
+    fn new(factor: u128) -> Result<Self, InvalidExpandFactor> {
+        if factor == 0 {
+            return Err(InvalidExpandFactor);
         }
+
+        return Ok(ExpandFactor(factor));
     }
 
-    return galaxies;
+    // The lines added for every empty line.
+    fn added(&self) -> u128 {
+        return self.0 - 1;
+    }
 }
 
-fn find_distances_sum(galaxies: &Vec<(usize, usize)>) -> Vec<Vec<usize>> {
-    let mut result: Vec<Vec<usize>> = Vec::new();
+fn count_before(sorted_lines: &[usize], coordinate: usize) -> usize {
+    return sorted_lines.partition_point(|line| *line < coordinate);
+}
+
+fn expand_coordinate(
+    sorted_lines: &[usize],
+    coordinate: usize,
+    expand_factor: ExpandFactor,
+) -> u128 {
+    return coordinate as u128
+        + expand_factor.added() * count_before(sorted_lines, coordinate) as u128;
+}
 
-    for i in 0..galaxies.len() - 1 {
-        result.push(Vec::new());
-        for j in i + 1..galaxies.len() {
-            let distance =
-                galaxies[i].0.abs_diff(galaxies[j].0) + galaxies[i].1.abs_diff(galaxies[j].1);
-            result[i].push(distance);
+// Inverse of expand_coordinate, None when the expanded coordinate lies past
+// the last original line.
+fn original_coordinate(
+    sorted_lines: &[usize],
+    length: usize,
+    expanded: u128,
+    expand_factor: ExpandFactor,
+) -> Option<usize> {
+    let expanded_start = |k: usize| sorted_lines[k] as u128 + expand_factor.added() * k as u128;
+
+    let mut low = 0;
+    let mut high = sorted_lines.len();
+    while low < high {
+        let middle = (low + high) / 2;
+        if expanded_start(middle) <= expanded {
+            low = middle + 1;
+        } else {
+            high = middle;
         }
     }
 
-    return result;
-}
+    if low > 0 && expanded < expanded_start(low - 1) + expand_factor.0 {
+        return Some(sorted_lines[low - 1]);
+    }
 
-fn expand(space: &Vec<Vec<Tile>>) -> Vec<Vec<Tile>> {
-    let expanded_columns = expand_columns(&space);
+    let coordinate = expanded - expand_factor.added() * low as u128;
+    if coordinate < length as u128 {
+        return Some(coordinate as usize);
+    }
 
-    return expand_rows(&expanded_columns);
+    return None;
 }
 
-fn rows_to_expand(space: &Vec<Vec<Tile>>) -> HashSet<usize> {
-    let mut expanded = HashSet::new();
+fn axis_distance_sum(coordinates: &mut [u128]) -> u128 {
+    coordinates.sort();
 
-    for (i, row) in space.iter().enumerate() {
-        if row.iter().all(|tile| *tile == Tile::Empty) {
-            expanded.insert(i);
-        }
+    let mut sum = 0;
+    let mut prefix = 0;
+    for (i, coordinate) in coordinates.iter().enumerate() {
+        sum += coordinate * i as u128 - prefix;
+        prefix += coordinate;
     }
 
-    return expanded;
+    return sum;
 }
 
-fn columns_to_expand(space: &Vec<Vec<Tile>>) -> HashSet<usize> {
-    let mut expanded = HashSet::new();
+#[derive(Debug)]
+struct Universe {
+    space: Vec<Vec<Tile>>,
+    rows_to_expand: Vec<usize>,
+    columns_to_expand: Vec<usize>,
+}
 
-    for j in 0..space[0].len() {
-        let mut to_expand = true;
-        for i in 0..space.len() {
-            if space[i][j] == Tile::Galaxy {
-                to_expand = false;
-                break;
-            }
-        }
+impl Universe {
+    fn new(space: Vec<Vec<Tile>>) -> Universe {
+        let rows_to_expand = rows_to_expand(&space);
+        let columns_to_expand = columns_to_expand(&space);
 
-        if to_expand {
-            expanded.insert(j);
-        }
+        return Universe {
+            space,
+            rows_to_expand,
+            columns_to_expand,
+        };
     }
 
-    return expanded;
-}
+    fn height(&self) -> usize {
+        return self.space.len();
+    }
 
-fn find_distances_sum_sets(
-    galaxies: &Vec<(usize, usize)>,
-    expanded_rows: &HashSet<usize>,
-    expanded_columns: &HashSet<usize>,
-    expand_factor: usize,
-) -> Vec<Vec<usize>> {
-    let mut result: Vec<Vec<usize>> = Vec::new();
-
-    for i in 0..galaxies.len() - 1 {
-        result.push(Vec::new());
-        for j in i + 1..galaxies.len() {
-            let mut expanded_rows_count = 0;
-            for k in min(galaxies[i].0, galaxies[j].0)..max(galaxies[i].0, galaxies[j].0) {
-                if expanded_rows.contains(&k) {
-                    expanded_rows_count += 1;
-                }
-            }
+    fn width(&self) -> usize {
+        return self.space.first().map_or(0, |row| row.len());
+    }
+
+    fn expanded_position(
+        &self,
+        position: (usize, usize),
+        expand_factor: ExpandFactor,
+    ) -> (u128, u128) {
+        return (
+            expand_coordinate(&self.rows_to_expand, position.0, expand_factor),
+            expand_coordinate(&self.columns_to_expand, position.1, expand_factor),
+        );
+    }
+
+    fn original_position(
+        &self,
+        expanded: (u128, u128),
+        expand_factor: ExpandFactor,
+    ) -> Option<(usize, usize)> {
+        let i = original_coordinate(
+            &self.rows_to_expand,
+            self.height(),
+            expanded.0,
+            expand_factor,
+        )?;
+        let j = original_coordinate(
+            &self.columns_to_expand,
+            self.width(),
+            expanded.1,
+            expand_factor,
+        )?;
+
+        return Some((i, j));
+    }
+
+    fn galaxies(&self, expand_factor: ExpandFactor) -> Vec<(u128, u128)> {
+        return find_galaxies(&self.space)
+            .into_iter()
+            .map(|galaxy| self.expanded_position(galaxy, expand_factor))
+            .collect();
+    }
+
+    fn distance_sum(&self, expand_factor: ExpandFactor) -> u128 {
+        let galaxies = self.galaxies(expand_factor);
+        let mut rows: Vec<u128> = galaxies.iter().map(|(i, _)| *i).collect();
+        let mut columns: Vec<u128> = galaxies.iter().map(|(_, j)| *j).collect();
+
+        return axis_distance_sum(&mut rows) + axis_distance_sum(&mut columns);
+    }
 
-            let mut expanded_columns_count = 0;
-            for k in min(galaxies[i].1, galaxies[j].1)..max(galaxies[i].1, galaxies[j].1) {
-                if expanded_columns.contains(&k) {
-                    expanded_columns_count += 1;
+    fn galaxy_distance(
+        &self,
+        first: (usize, usize),
+        second: (usize, usize),
+        expand_factor: ExpandFactor,
+    ) -> u128 {
+        let first = self.expanded_position(first, expand_factor);
+        let second = self.expanded_position(second, expand_factor);
+
+        return first.0.abs_diff(second.0) + first.1.abs_diff(second.1);
+    }
+
+    fn render(
+        &self,
+        expand_factor: ExpandFactor,
+        top_left: (u128, u128),
+        height: usize,
+        width: usize,
+    ) -> String {
+        let mut rendered = String::new();
+
+        for di in 0..height as u128 {
+            let mut line = String::new();
+            for dj in 0..width as u128 {
+                let position = (top_left.0 + di, top_left.1 + dj);
+                match self.original_position(position, expand_factor) {
+                    Some((i, j)) if self.space[i][j] == Tile::Galaxy => line.push('#'),
+                    Some(_) => line.push('.'),
+                    None => break,
                 }
             }
 
-            let distance = galaxies[i].0.abs_diff(galaxies[j].0)
-                + galaxies[i].1.abs_diff(galaxies[j].1)
-                + (expand_factor - 1) * (expanded_columns_count + expanded_rows_count);
+            if line.is_empty() {
+                break;
+            }
 
-            result[i].push(distance);
+            rendered.push_str(&line);
+            rendered.push('\n');
         }
-    }
 
-    return result;
+        return rendered;
+    }
 }
 
 fn parse_space(s: &str) -> Vec<Vec<Tile>> {
@@ -164,33 +262,83 @@ fn parse_space(s: &str) -> Vec<Vec<Tile>> {
         .collect();
 }
 
-fn get_sum(distances: &Vec<Vec<usize>>) -> usize {
-    return distances
-        .iter()
-        .map(|dist_from_galaxy| dist_from_galaxy.iter().sum::<usize>())
-        .sum();
+// The expansion factor, top-left corner, height and width of a viewport.
+fn parse_viewport(args: &[String]) -> Option<(ExpandFactor, (u128, u128), usize, usize)> {
+    if args.len() != 5 {
+        return None;
+    }
+
+    let expand_factor = ExpandFactor::new(args[0].parse().ok()?).ok()?;
+
+    let top_left = (args[1].parse().ok()?, args[2].parse().ok()?);
+    return Some((
+        expand_factor,
+        top_left,
+        args[3].parse().ok()?,
+        args[4].parse().ok()?,
+    ));
+}
+
+// The expansion factor and the numbers of two galaxies, counted from 1 in
+// reading order like in the puzzle.
+fn parse_galaxy_pair(args: &[String]) -> Option<(ExpandFactor, usize, usize)> {
+    if args.len() != 3 {
+        return None;
+    }
+
+    let expand_factor = ExpandFactor::new(args[0].parse().ok()?).ok()?;
+    let first: usize = args[1].parse().ok()?;
+    let second: usize = args[2].parse().ok()?;
+    if first == 0 || second == 0 {
+        return None;
+    }
+
+    return Some((expand_factor, first, second));
 }
 
 fn main() {
     let input = fs::read_to_string("input").unwrap();
-    let space = parse_space(&input);
-    let expanded_space = expand(&space);
-    let galaxies = find_galaxies(&expanded_space);
-
-    println!("{}", get_sum(&find_distances_sum(&galaxies)));
+    let universe = Universe::new(parse_space(&input));
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "render") {
+        let Some((expand_factor, top_left, height, width)) = parse_viewport(&args[1..]) else {
+            eprintln!("usage: render <factor> <row> <column> <height> <width>");
+            process::exit(1);
+        };
+
+        print!(
+            "{}",
+            universe.render(expand_factor, top_left, height, width)
+        );
+        return;
+    }
 
-    let expanded_rows = rows_to_expand(&space);
-    let expanded_columns = columns_to_expand(&space);
-    let galaxies_non_expanded = find_galaxies(&space);
+    if args.first().is_some_and(|arg| arg == "distance") {
+        let galaxies = find_galaxies(&universe.space);
+        let pair = parse_galaxy_pair(&args[1..]).and_then(|(expand_factor, first, second)| {
+            Some((
+                expand_factor,
+                *galaxies.get(first - 1)?,
+                *galaxies.get(second - 1)?,
+            ))
+        });
+        let Some((expand_factor, first, second)) = pair else {
+            eprintln!(
+                "usage: distance <factor> <galaxy> <galaxy>, galaxies numbered 1 to {}",
+                galaxies.len()
+            );
+            process::exit(1);
+        };
+
+        println!("{}", universe.galaxy_distance(first, second, expand_factor));
+        return;
+    }
 
+    println!("{}", universe.distance_sum(ExpandFactor::new(2).unwrap()));
     println!(
         "{}",
-        get_sum(&find_distances_sum_sets(
-            &galaxies_non_expanded,
-            &expanded_rows,
-            &expanded_columns,
-            1000000
-        ))
+        universe.distance_sum(ExpandFactor::new(1000000).unwrap())
     );
 }
 
@@ -199,15 +347,25 @@ mod tests {
     use std::{collections::HashSet, fs};
 
     use crate::{
-        columns_to_expand, expand, find_distances_sum, find_distances_sum_sets, find_galaxies,
-        get_sum, parse_space, rows_to_expand, Tile,
+        columns_to_expand, find_galaxies, parse_galaxy_pair, parse_space, parse_viewport,
+        rows_to_expand, ExpandFactor, InvalidExpandFactor, Tile, Universe,
     };
 
+    fn factor(factor: u128) -> ExpandFactor {
+        return ExpandFactor::new(factor).unwrap();
+    }
+
+    fn get_universe(file: &str) -> Universe {
+        let input = fs::read_to_string(file).unwrap();
+
+        return Universe::new(parse_space(&input));
+    }
+
     #[test]
     fn test_parse() {
         let input = fs::read_to_string("test").unwrap();
         let space = parse_space(&input);
-        let galaxySet = HashSet::from([
+        let galaxy_set = HashSet::from([
             (0, 3),
             (1, 7),
             (2, 0),
@@ -219,12 +377,12 @@ mod tests {
             (9, 4),
         ]);
 
-        for i in 0..space.len() {
-            for j in 0..space[i].len() {
-                if galaxySet.contains(&(i, j)) {
-                    assert_eq!(space[i][j], Tile::Galaxy);
+        for (i, row) in space.iter().enumerate() {
+            for (j, tile) in row.iter().enumerate() {
+                if galaxy_set.contains(&(i, j)) {
+                    assert_eq!(*tile, Tile::Galaxy);
                 } else {
-                    assert_eq!(space[i][j], Tile::Empty);
+                    assert_eq!(*tile, Tile::Empty);
                 }
             }
         }
@@ -232,14 +390,62 @@ mod tests {
 
     #[test]
     fn test_expand() {
-        let input = fs::read_to_string("test").unwrap();
-        let space = parse_space(&input);
-        let expanded_space = expand(&space);
+        let universe = get_universe("test");
 
         let expanded_input = fs::read_to_string("test_expanded").unwrap();
-        let expected_expanded = parse_space(&expanded_input);
+        let rendered = universe.render(factor(2), (0, 0), 100, 100);
+
+        assert_eq!(parse_space(&rendered), parse_space(&expanded_input));
+    }
+
+    #[test]
+    fn test_render_viewport() {
+        let universe = get_universe("test");
+
+        assert_eq!(
+            universe.render(factor(2), (1, 8), 3, 4),
+            ".#..\n....\n....\n"
+        );
+        assert_eq!(universe.render(factor(3), (12, 0), 5, 3), "...\n#..\n");
+        assert_eq!(
+            universe.render(factor(1000000), (2000005, 1000001), 3, 4),
+            "....\n....\n..#.\n"
+        );
+        assert_eq!(universe.render(factor(2), (20, 0), 3, 3), "");
+    }
+
+    #[test]
+    fn test_expand_factor() {
+        assert_eq!(ExpandFactor::new(0), Err(InvalidExpandFactor));
 
-        assert_eq!(expanded_space, expected_expanded);
+        // A factor of one leaves the universe as it is.
+        let universe = get_universe("test");
+        assert_eq!(universe.expanded_position((9, 4), factor(1)), (9, 4));
+        assert_eq!(universe.distance_sum(factor(1)), 292);
+    }
+
+    #[test]
+    fn test_parse_galaxy_pair() {
+        let args = |s: &str| -> Vec<String> { s.split(' ').map(String::from).collect() };
+
+        assert_eq!(parse_galaxy_pair(&args("2 5 9")), Some((factor(2), 5, 9)));
+        assert_eq!(parse_galaxy_pair(&args("0 5 9")), None);
+        assert_eq!(parse_galaxy_pair(&args("2 0 9")), None);
+        assert_eq!(parse_galaxy_pair(&args("2 5")), None);
+        assert_eq!(parse_galaxy_pair(&args("2 5 x")), None);
+    }
+
+    #[test]
+    fn test_parse_viewport() {
+        let args = |s: &str| -> Vec<String> { s.split(' ').map(String::from).collect() };
+
+        assert_eq!(
+            parse_viewport(&args("2 1 8 3 4")),
+            Some((factor(2), (1, 8), 3, 4))
+        );
+        assert_eq!(parse_viewport(&args("0 1 8 3 4")), None);
+        assert_eq!(parse_viewport(&args("2 1 8 3")), None);
+        assert_eq!(parse_viewport(&args("2 1 -8 3 4")), None);
     }
 
     #[test]
@@ -262,13 +468,33 @@ mod tests {
     }
 
     #[test]
-    fn test_find_distances() {
-        let input = fs::read_to_string("test").unwrap();
-        let space = parse_space(&input);
-        let expanded_space = expand(&space);
-        let galaxies = find_galaxies(&expanded_space);
+    fn test_expanded_position() {
+        let universe = get_universe("test");
+
+        assert_eq!(universe.expanded_position((0, 3), factor(2)), (0, 4));
+        assert_eq!(universe.expanded_position((9, 4), factor(2)), (11, 5));
+        assert_eq!(universe.expanded_position((9, 4), factor(10)), (27, 13));
+        assert_eq!(
+            universe.original_position((27, 13), factor(10)),
+            Some((9, 4))
+        );
+        assert_eq!(universe.original_position((5, 5), factor(10)), Some((3, 2)));
+        assert_eq!(universe.original_position((28, 0), factor(10)), None);
+    }
 
-        assert_eq!(get_sum(&find_distances_sum(&galaxies)), 374);
+    #[test]
+    fn test_find_distances() {
+        let universe = get_universe("test");
+        let galaxies = universe.galaxies(factor(2));
+
+        assert_eq!(
+            find_galaxies(&parse_space(&fs::read_to_string("test_expanded").unwrap()))
+                .iter()
+                .map(|(i, j)| (*i as u128, *j as u128))
+                .collect::<Vec<(u128, u128)>>(),
+            galaxies
+        );
+        assert_eq!(universe.distance_sum(factor(2)), 374);
     }
 
     #[test]
@@ -277,7 +503,7 @@ mod tests {
         let space = parse_space(&input);
         let expanded_rows = rows_to_expand(&space);
 
-        assert_eq!(expanded_rows, HashSet::from([3, 7]));
+        assert_eq!(expanded_rows, vec![3, 7]);
     }
 
     #[test]
@@ -286,18 +512,46 @@ mod tests {
         let space = parse_space(&input);
         let expanded_rows = columns_to_expand(&space);
 
-        assert_eq!(expanded_rows, HashSet::from([2, 5, 8]));
+        assert_eq!(expanded_rows, vec![2, 5, 8]);
     }
 
     #[test]
     fn test_find_distances_sum_set() {
-        let input = fs::read_to_string("test").unwrap();
-        let space = parse_space(&input);
-        let galaxies = find_galaxies(&space);
-        let expanded_rows = rows_to_expand(&space);
-        let expanded_columns = columns_to_expand(&space);
+        let universe = get_universe("test");
+
+        assert_eq!(universe.distance_sum(factor(2)), 374);
+        assert_eq!(universe.distance_sum(factor(10)), 1030);
+        assert_eq!(universe.distance_sum(factor(100)), 8410);
+        assert_eq!(
+            universe.distance_sum(factor(1 << 100)),
+            292 + 82 * ((1 << 100) - 1)
+        );
+    }
+
+    #[test]
+    fn test_expanded_distance_sum_matches_pairs() {
+        let universe = get_universe("input");
+        let galaxies = find_galaxies(&universe.space);
+
+        for expand_factor in [1, 2, 1000000].map(factor) {
+            let mut pairwise_sum = 0;
+            for i in 0..galaxies.len() {
+                for j in i + 1..galaxies.len() {
+                    pairwise_sum +=
+                        universe.galaxy_distance(galaxies[i], galaxies[j], expand_factor);
+                }
+            }
+
+            assert_eq!(universe.distance_sum(expand_factor), pairwise_sum);
+        }
+    }
+
+    #[test]
+    fn test_galaxy_distance() {
+        let universe = get_universe("test");
 
-        let sum_sets = find_distances_sum_sets(&galaxies, &expanded_rows, &expanded_columns, 2);
-        assert_eq!(get_sum(&sum_sets), 374);
+        assert_eq!(universe.galaxy_distance((5, 1), (9, 4), factor(2)), 9);
+        assert_eq!(universe.galaxy_distance((0, 3), (8, 7), factor(2)), 15);
+        assert_eq!(universe.galaxy_distance((9, 0), (9, 4), factor(2)), 5);
     }
 }
@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return)]
+
 use std::fs;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -23,11 +25,12 @@ fn parse_line(s: &str) -> Result<Vec<Element>, ParseSubPatternError> {
 fn parse_pattern(input: &str) -> Result<Vec<Vec<Element>>, ParseSubPatternError> {
     return input
         .lines()
-        .map(|line| parse_line(line))
+        .map(parse_line)
         .collect::<Result<Vec<Vec<Element>>, ParseSubPatternError>>();
 }
 
-fn transpose(pattern: &Vec<Vec<Element>>) -> Vec<Vec<Element>> {
+#[cfg(test)]
+fn transpose(pattern: &[Vec<Element>]) -> Vec<Vec<Element>> {
     let mut transposed = vec![vec![Element::Ash; pattern.len()]; pattern[0].len()];
 
     for i in 0..pattern.len() {
@@ -39,8 +42,9 @@ fn transpose(pattern: &Vec<Vec<Element>>) -> Vec<Vec<Element>> {
     return transposed;
 }
 
+#[cfg(test)]
 fn check_expand(
-    pattern: &Vec<Vec<Element>>,
+    pattern: &[Vec<Element>],
     left: i64,
     right: usize,
     error_threshold: usize,
@@ -50,11 +54,11 @@ fn check_expand(
 
     let mut errors = 0;
     while left >= 0 && right < pattern.len() {
-        for j in 0..pattern[0].len() {
-            if pattern[left as usize][j] != pattern[right][j] {
-                errors += 1;
-            }
-        }
+        errors += pattern[left as usize]
+            .iter()
+            .zip(pattern[right].iter())
+            .filter(|(a, b)| a != b)
+            .count();
 
         if errors > error_threshold {
             break;
@@ -88,7 +92,8 @@ fn get_indexes_from_middle(length: usize) -> Vec<usize> {
     return rows_to_check;
 }
 
-fn get_mirror(pattern: &Vec<Vec<Element>>, error_threshold: usize) -> usize {
+#[cfg(test)]
+fn get_mirror(pattern: &[Vec<Element>], error_threshold: usize) -> usize {
     for i in get_indexes_from_middle(pattern.len()) {
         if i == pattern.len() - 1 {
             continue;
@@ -103,18 +108,89 @@ fn get_mirror(pattern: &Vec<Vec<Element>>, error_threshold: usize) -> usize {
     return 0;
 }
 
+const WORD_BITS: usize = u64::BITS as usize;
+
+#[derive(Debug, PartialEq, Eq)]
+struct BitPattern {
+    rows: Vec<Vec<u64>>,
+    columns: Vec<Vec<u64>>,
+}
+
+impl BitPattern {
+    fn from_pattern(pattern: &[Vec<Element>]) -> BitPattern {
+        let height = pattern.len();
+        let width = pattern.first().map_or(0, |row| row.len());
+
+        let mut rows = vec![vec![0u64; width.div_ceil(WORD_BITS)]; height];
+        let mut columns = vec![vec![0u64; height.div_ceil(WORD_BITS)]; width];
+
+        for (i, row) in pattern.iter().enumerate() {
+            for (j, element) in row.iter().enumerate() {
+                if *element == Element::Rock {
+                    rows[i][j / WORD_BITS] |= 1 << (j % WORD_BITS);
+                    columns[j][i / WORD_BITS] |= 1 << (i % WORD_BITS);
+                }
+            }
+        }
+
+        return BitPattern { rows, columns };
+    }
+
+    fn row_mirror(&self, error_threshold: usize) -> usize {
+        return get_bit_mirror(&self.rows, error_threshold);
+    }
+
+    fn column_mirror(&self, error_threshold: usize) -> usize {
+        return get_bit_mirror(&self.columns, error_threshold);
+    }
+}
+
+fn count_smudges(first: &[u64], second: &[u64]) -> usize {
+    return first
+        .iter()
+        .zip(second.iter())
+        .map(|(a, b)| (a ^ b).count_ones() as usize)
+        .sum();
+}
+
+fn check_expand_bits(lines: &[Vec<u64>], left: usize, error_threshold: usize) -> usize {
+    let mut errors = 0;
+    for (upper, lower) in lines[..=left].iter().rev().zip(lines[left + 1..].iter()) {
+        errors += count_smudges(upper, lower);
+
+        if errors > error_threshold {
+            break;
+        }
+    }
+
+    return errors;
+}
+
+fn get_bit_mirror(lines: &[Vec<u64>], error_threshold: usize) -> usize {
+    for i in get_indexes_from_middle(lines.len()) {
+        if i == lines.len() - 1 {
+            continue;
+        }
+
+        if check_expand_bits(lines, i, error_threshold) == error_threshold {
+            return i + 1;
+        }
+    }
+
+    return 0;
+}
+
 fn main() {
     let input = fs::read_to_string("input.txt").unwrap();
 
     let mut sum = 0;
     for pattern_str in input.split("\n\n") {
-        let pattern = parse_pattern(pattern_str).unwrap();
+        let pattern = BitPattern::from_pattern(&parse_pattern(pattern_str).unwrap());
 
-        let rows = get_mirror(&pattern, 1);
+        let rows = pattern.row_mirror(1);
 
         if rows == 0 {
-            let columns = get_mirror(&transpose(&pattern), 1);
-            sum += columns;
+            sum += pattern.column_mirror(1);
         } else {
             sum += rows * 100;
         }
@@ -125,9 +201,11 @@ fn main() {
 
 #[cfg(test)]
 mod tests {
-    use std::fs;
+    use std::{fs, time::Instant};
 
-    use crate::{get_indexes_from_middle, get_mirror, parse_pattern, transpose, Element};
+    use crate::{
+        get_indexes_from_middle, get_mirror, parse_pattern, transpose, BitPattern, Element,
+    };
 
     fn get_pattern(file: &str) -> Vec<Vec<Element>> {
         let input = fs::read_to_string(file).unwrap();
@@ -177,4 +255,77 @@ mod tests {
         );
         assert_eq!(get_mirror(&transpose(&get_pattern("test_row_1.txt")), 0), 0);
     }
+
+    #[test]
+    fn test_bit_pattern_matches_cell_by_cell() {
+        let input = fs::read_to_string("input.txt").unwrap();
+
+        for pattern_str in input.split("\n\n") {
+            let pattern = parse_pattern(pattern_str).unwrap();
+            let bits = BitPattern::from_pattern(&pattern);
+
+            for error_threshold in 0..=1 {
+                assert_eq!(
+                    bits.row_mirror(error_threshold),
+                    get_mirror(&pattern, error_threshold)
+                );
+                assert_eq!(
+                    bits.column_mirror(error_threshold),
+                    get_mirror(&transpose(&pattern), error_threshold)
+                );
+            }
+        }
+    }
+
+    fn generate_pattern(size: usize, mirror: usize, seed: u64) -> Vec<Vec<Element>> {
+        let mut state = seed;
+        let mut next_element = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            if state & 1 == 0 {
+                Element::Ash
+            } else {
+                Element::Rock
+            }
+        };
+
+        let mut pattern: Vec<Vec<Element>> = (0..size)
+            .map(|_| (0..size).map(|_| next_element()).collect())
+            .collect();
+        for i in 0..mirror.min(size - mirror) {
+            pattern[mirror + i] = pattern[mirror - 1 - i].clone();
+        }
+
+        return pattern;
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture`"]
+    fn bench_large_patterns() {
+        let patterns: Vec<Vec<Vec<Element>>> = (0..10)
+            .map(|i| generate_pattern(1000, 400 + i * 20, 0x9E3779B97F4A7C15 + i as u64))
+            .collect();
+
+        let start = Instant::now();
+        let expected: Vec<usize> = patterns.iter().map(|p| get_mirror(p, 0)).collect();
+        println!("cell by cell: {:?}", start.elapsed());
+
+        let start = Instant::now();
+        let bits: Vec<BitPattern> = patterns
+            .iter()
+            .map(|p| BitPattern::from_pattern(p))
+            .collect();
+        println!("bit encoding: {:?}", start.elapsed());
+
+        let start = Instant::now();
+        let actual: Vec<usize> = bits.iter().map(|p| p.row_mirror(0)).collect();
+        println!("bitmask: {:?}", start.elapsed());
+
+        assert_eq!(actual, expected);
+        assert_eq!(
+            actual,
+            (0..10).map(|i| 400 + i * 20).collect::<Vec<usize>>()
+        );
+    }
 }
@@ -1,4 +1,11 @@
-use std::{fmt::Display, fs};
+#![allow(clippy::needless_return)]
+
+use std::{
+    cmp::{max, min},
+    env,
+    fmt::Display,
+    fs,
+};
 
 #[derive(Debug, PartialEq, Eq)]
 enum Direction {
@@ -9,23 +16,23 @@ enum Direction {
 }
 
 impl Direction {
-    fn parse(s: &str) -> Direction {
+    fn parse(s: &str) -> Option<Direction> {
         match s {
-            "U" => Direction::Up,
-            "R" => Direction::Right,
-            "D" => Direction::Down,
-            "L" => Direction::Left,
-            _ => panic!("Unknown direction: {}", s),
+            "U" => Some(Direction::Up),
+            "R" => Some(Direction::Right),
+            "D" => Some(Direction::Down),
+            "L" => Some(Direction::Left),
+            _ => None,
         }
     }
 
-    fn parse_2(ch: &char) -> Direction {
+    fn parse_2(ch: &char) -> Option<Direction> {
         match ch {
-            '3' => Direction::Up,
-            '0' => Direction::Right,
-            '1' => Direction::Down,
-            '2' => Direction::Left,
-            _ => panic!("Unknown direction: {}", ch),
+            '3' => Some(Direction::Up),
+            '0' => Some(Direction::Right),
+            '1' => Some(Direction::Down),
+            '2' => Some(Direction::Left),
+            _ => None,
         }
     }
 }
@@ -45,6 +52,7 @@ impl Display for Direction {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 struct Point {
     x: i64,
     y: i64,
@@ -73,63 +81,417 @@ impl Point {
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
 struct Step {
     direction: Direction,
     length: i64,
+    colour: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ParseStepError {
+    Direction,
+    Length,
+    Colour,
+}
+
+fn parse_colour(s: &str) -> Result<&str, ParseStepError> {
+    let hex = s
+        .strip_prefix("(#")
+        .and_then(|hex| hex.strip_suffix(')'))
+        .ok_or(ParseStepError::Colour)?;
+
+    if hex.len() != 6 || !hex.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return Err(ParseStepError::Colour);
+    }
+
+    return Ok(hex);
 }
 
 impl Step {
-    fn parse(s: &str) -> Self {
-        let split = s.split(" ").collect::<Vec<_>>();
+    fn parse(s: &str) -> Result<Self, ParseStepError> {
+        let mut split = s.split(' ');
 
-        Self {
-            direction: Direction::parse(split[0]),
-            length: split[1].parse::<i64>().unwrap(),
-        }
+        let direction = split
+            .next()
+            .and_then(Direction::parse)
+            .ok_or(ParseStepError::Direction)?;
+
+        let length = split
+            .next()
+            .and_then(|length| length.parse::<i64>().ok())
+            .filter(|length| *length > 0)
+            .ok_or(ParseStepError::Length)?;
+
+        let colour = parse_colour(split.next().ok_or(ParseStepError::Colour)?)?;
+
+        Ok(Self {
+            direction,
+            length,
+            colour: colour.to_string(),
+        })
     }
 
-    fn parse_2(s: &str) -> Self {
-        let hex = s.split(" ").last().unwrap();
-        let hex = hex.strip_prefix("(#").unwrap().strip_suffix(")").unwrap();
+    fn parse_2(s: &str) -> Result<Self, ParseStepError> {
+        let hex = parse_colour(s.split(' ').next_back().unwrap())?;
 
         let last_char = hex.chars().last().unwrap();
-        Self {
-            direction: Direction::parse_2(&last_char),
-            length: i64::from_str_radix(hex.strip_suffix(last_char).unwrap(), 16).unwrap(),
+        let length = i64::from_str_radix(&hex[..5], 16).unwrap();
+        if length == 0 {
+            return Err(ParseStepError::Length);
         }
+
+        Ok(Self {
+            direction: Direction::parse_2(&last_char).ok_or(ParseStepError::Direction)?,
+            length,
+            colour: hex.to_string(),
+        })
     }
 }
 
-fn get_area(steps: &Vec<Step>) -> u64 {
-    let mut area = 0;
-    let mut p1 = Point { x: 0, y: 0 };
+#[derive(Debug)]
+struct Polygon {
+    vertices: Vec<Point>,
+    colours: Vec<String>,
+}
+
+impl Polygon {
+    fn from_steps(steps: &[Step]) -> Polygon {
+        let mut vertices = vec![Point { x: 0, y: 0 }];
+
+        for step in steps {
+            let next = vertices
+                .last()
+                .unwrap()
+                .get_next(&step.direction, step.length);
+            vertices.push(next);
+        }
+
+        return Polygon {
+            vertices,
+            colours: steps.iter().map(|step| step.colour.clone()).collect(),
+        };
+    }
+
+    fn bounds(&self) -> (Point, Point) {
+        let min_x = self.vertices.iter().map(|p| p.x).min().unwrap();
+        let max_x = self.vertices.iter().map(|p| p.x).max().unwrap();
+        let min_y = self.vertices.iter().map(|p| p.y).min().unwrap();
+        let max_y = self.vertices.iter().map(|p| p.y).max().unwrap();
+
+        return (Point { x: min_x, y: min_y }, Point { x: max_x, y: max_y });
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (&Point, &Point)> {
+        return self.vertices.iter().zip(self.vertices.iter().skip(1));
+    }
+
+    fn is_closed(&self) -> bool {
+        return self.vertices.first() == self.vertices.last();
+    }
 
-    for step in steps {
-        let p2 = p1.get_next(&step.direction, step.length);
+    fn boundary_length(&self) -> u64 {
+        return self
+            .edges()
+            .map(|(p1, p2)| p1.x.abs_diff(p2.x) + p1.y.abs_diff(p2.y))
+            .sum();
+    }
 
-        // we have to add length
-        // because the area is up to the point,
-        // so we have to include the edge as well
-        area += p1.x * p2.y - p1.y * p2.x + step.length;
+    fn double_area(&self) -> u64 {
+        return self
+            .edges()
+            .map(|(p1, p2)| p1.x * p2.y - p1.y * p2.x)
+            .sum::<i64>()
+            .unsigned_abs();
+    }
+
+    // Pick's theorem: A = I + B / 2 - 1
+    fn interior_points(&self) -> u64 {
+        return (self.double_area() + 2 - self.boundary_length()) / 2;
+    }
 
-        p1 = p2;
+    fn lagoon_size(&self) -> u64 {
+        return self.interior_points() + self.boundary_length();
     }
 
-    //accounting for the 0,0 block
-    //that is why it's + 1
-    return 1 + (area.abs() / 2) as u64;
+    // Pairs of edge indexes that touch anywhere other than the vertex shared
+    // by consecutive edges.
+    fn self_intersections(&self) -> Vec<(usize, usize)> {
+        let edges: Vec<(&Point, &Point)> = self.edges().collect();
+        let mut intersections = Vec::new();
+
+        for i in 0..edges.len() {
+            for j in i + 1..edges.len() {
+                let consecutive =
+                    j == i + 1 || (self.is_closed() && i == 0 && j == edges.len() - 1);
+                let touches = if consecutive {
+                    overlaps_backwards(edges[i], edges[j])
+                } else {
+                    segments_touch(edges[i], edges[j])
+                };
+
+                if touches {
+                    intersections.push((i, j));
+                }
+            }
+        }
+
+        return intersections;
+    }
+
+    fn contains(&self, point: &Point) -> bool {
+        let mut inside = false;
+
+        for (p1, p2) in self.edges() {
+            if segments_touch((p1, p2), (point, point)) {
+                return true;
+            }
+
+            if p1.x == p2.x
+                && p1.x > point.x
+                && min(p1.y, p2.y) <= point.y
+                && point.y < max(p1.y, p2.y)
+            {
+                inside = !inside;
+            }
+        }
+
+        return inside;
+    }
+
+    fn render_preview(&self, width: usize, height: usize) -> String {
+        let (Point { x: min_x, y: min_y }, Point { x: max_x, y: max_y }) = self.bounds();
+
+        let width = min(width as i64, max_x - min_x + 1);
+        let height = min(height as i64, max_y - min_y + 1);
+
+        let mut preview = String::new();
+        for row in 0..height {
+            for column in 0..width {
+                let point = Point {
+                    x: min_x + (max_x - min_x) * column / max(width - 1, 1),
+                    y: min_y + (max_y - min_y) * row / max(height - 1, 1),
+                };
+
+                preview.push(if self.contains(&point) { '#' } else { '.' });
+            }
+            preview.push('\n');
+        }
+
+        return preview;
+    }
+
+    // Trench cells are centered on the lattice points, so the path runs
+    // through the vertices and the strokes are one cell wide.
+    fn to_svg(&self) -> String {
+        let (low, high) = self.bounds();
+        let stroke = max(1, max(high.x - low.x, high.y - low.y) / 500);
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+            low.x - stroke,
+            low.y - stroke,
+            high.x - low.x + 2 * stroke,
+            high.y - low.y + 2 * stroke
+        );
+
+        let path = self
+            .vertices
+            .iter()
+            .map(|p| format!("{},{}", p.x, p.y))
+            .collect::<Vec<String>>()
+            .join(" L");
+        svg.push_str(&format!(
+            "  <path d=\"M{} Z\" fill=\"#888888\" fill-opacity=\"0.5\" stroke=\"none\"/>\n",
+            path
+        ));
+
+        for ((p1, p2), colour) in self.edges().zip(self.colours.iter()) {
+            svg.push_str(&format!(
+                "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#{}\" stroke-width=\"{}\" stroke-linecap=\"square\"/>\n",
+                p1.x, p1.y, p2.x, p2.y, colour, stroke
+            ));
+        }
+
+        svg.push_str("</svg>\n");
+        return svg;
+    }
 }
 
-fn parse_steps(s: &str, parse_step: fn(&str) -> Step) -> Vec<Step> {
-    return s.lines().map(|line| parse_step(line)).collect();
+fn segments_touch(first: (&Point, &Point), second: (&Point, &Point)) -> bool {
+    return max(min(first.0.x, first.1.x), min(second.0.x, second.1.x))
+        <= min(max(first.0.x, first.1.x), max(second.0.x, second.1.x))
+        && max(min(first.0.y, first.1.y), min(second.0.y, second.1.y))
+            <= min(max(first.0.y, first.1.y), max(second.0.y, second.1.y));
+}
+
+// Consecutive edges always share a vertex, they only overlap when the second
+// one doubles back over the first.
+fn overlaps_backwards(first: (&Point, &Point), second: (&Point, &Point)) -> bool {
+    let dx1 = (first.1.x - first.0.x).signum();
+    let dy1 = (first.1.y - first.0.y).signum();
+    let dx2 = (second.1.x - second.0.x).signum();
+    let dy2 = (second.1.y - second.0.y).signum();
+
+    return dx1 == -dx2 && dy1 == -dy2;
+}
+
+fn parse_steps(
+    s: &str,
+    parse_step: fn(&str) -> Result<Step, ParseStepError>,
+) -> Result<Vec<Step>, ParseStepError> {
+    return s.lines().map(parse_step).collect();
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").unwrap();
-    let steps = parse_steps(&input, Step::parse);
+    let polygon = Polygon::from_steps(&parse_steps(&input, Step::parse).unwrap());
+    assert!(polygon.is_closed() && polygon.self_intersections().is_empty());
+
+    println!("{}", polygon.lagoon_size());
+
+    let export_svg = env::args().any(|arg| arg == "--svg");
+    if export_svg {
+        fs::write("lagoon.svg", polygon.to_svg()).unwrap();
+    }
+
+    let polygon = Polygon::from_steps(&parse_steps(&input, Step::parse_2).unwrap());
+    assert!(polygon.is_closed() && polygon.self_intersections().is_empty());
+    println!("{}", polygon.lagoon_size());
+
+    if export_svg {
+        fs::write("lagoon_2.svg", polygon.to_svg()).unwrap();
+    }
+
+    if env::args().any(|arg| arg == "--preview") {
+        print!("{}", polygon.render_preview(80, 40));
+    }
+}
 
-    println!("{}", get_area(&steps));
+#[cfg(test)]
+mod tests {
+    use std::fs;
 
-    let steps = parse_steps(&input, Step::parse_2);
-    println!("{}", get_area(&steps));
+    use crate::{parse_steps, Direction, ParseStepError, Polygon, Step};
+
+    fn get_polygon(file: &str, parse_step: fn(&str) -> Result<Step, ParseStepError>) -> Polygon {
+        let input = fs::read_to_string(file).unwrap();
+
+        return Polygon::from_steps(&parse_steps(&input, parse_step).unwrap());
+    }
+
+    #[test]
+    fn test_parse_step() {
+        assert_eq!(
+            Step::parse("R 6 (#70c710)"),
+            Ok(Step {
+                direction: Direction::Right,
+                length: 6,
+                colour: String::from("70c710")
+            })
+        );
+        assert_eq!(
+            Step::parse_2("R 6 (#70c710)"),
+            Ok(Step {
+                direction: Direction::Right,
+                length: 461937,
+                colour: String::from("70c710")
+            })
+        );
+
+        assert_eq!(Step::parse("X 6 (#70c710)"), Err(ParseStepError::Direction));
+        assert_eq!(Step::parse("R -6 (#70c710)"), Err(ParseStepError::Length));
+        assert_eq!(Step::parse("R 6 (#70c71)"), Err(ParseStepError::Colour));
+        assert_eq!(Step::parse("R 6"), Err(ParseStepError::Colour));
+        assert_eq!(
+            Step::parse_2("R 6 (#70c714)"),
+            Err(ParseStepError::Direction)
+        );
+        assert_eq!(Step::parse_2("R 6 (#70g710)"), Err(ParseStepError::Colour));
+        assert_eq!(Step::parse_2("R 6 (70c710)"), Err(ParseStepError::Colour));
+        assert_eq!(Step::parse_2("R 6 (#000000)"), Err(ParseStepError::Length));
+    }
+
+    #[test]
+    fn test_polygon() {
+        let polygon = get_polygon("test.txt", Step::parse);
+        assert!(polygon.is_closed());
+        assert_eq!(polygon.boundary_length(), 38);
+        assert_eq!(polygon.interior_points(), 24);
+        assert_eq!(polygon.lagoon_size(), 62);
+        assert!(polygon.self_intersections().is_empty());
+
+        let polygon = get_polygon("test.txt", Step::parse_2);
+        assert!(polygon.is_closed());
+        assert_eq!(polygon.lagoon_size(), 952408144115);
+
+        let polygon = get_polygon("test4.txt", Step::parse);
+        assert_eq!(polygon.boundary_length(), 20);
+        assert_eq!(polygon.interior_points(), 16);
+    }
+
+    #[test]
+    fn test_polygon_not_closed() {
+        let input = "R 5 (#70c710)\nD 5 (#70c710)\nL 5 (#70c710)";
+        let polygon = Polygon::from_steps(&parse_steps(input, Step::parse).unwrap());
+
+        assert!(!polygon.is_closed());
+    }
+
+    #[test]
+    fn test_self_intersections() {
+        assert!(get_polygon("test2.txt", Step::parse)
+            .self_intersections()
+            .is_empty());
+        assert!(get_polygon("test3.txt", Step::parse)
+            .self_intersections()
+            .is_empty());
+
+        let input = "R 4 (#70c710)\nD 2 (#70c710)\nL 2 (#70c710)\nU 4 (#70c710)\nL 2 (#70c710)\nD 2 (#70c710)";
+        let polygon = Polygon::from_steps(&parse_steps(input, Step::parse).unwrap());
+        assert!(polygon.is_closed());
+        assert_eq!(polygon.self_intersections(), vec![(0, 3)]);
+
+        let input = "R 4 (#70c710)\nL 2 (#70c710)\nD 2 (#70c710)\nL 2 (#70c710)\nU 2 (#70c710)";
+        let polygon = Polygon::from_steps(&parse_steps(input, Step::parse).unwrap());
+        assert_eq!(polygon.self_intersections(), vec![(0, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn test_render_preview() {
+        let polygon = get_polygon("test4.txt", Step::parse);
+        assert_eq!(
+            polygon.render_preview(10, 10),
+            "######\n######\n######\n######\n######\n######\n"
+        );
+        assert_eq!(polygon.render_preview(3, 2), "###\n###\n");
+
+        let polygon = get_polygon("test.txt", Step::parse);
+        assert_eq!(
+            polygon.render_preview(7, 10),
+            "#######\n#######\n#######\n..#####\n..#####\n#######\n#####..\n#######\n.######\n.######\n"
+        );
+    }
+
+    #[test]
+    fn test_to_svg() {
+        let input = "R 2 (#70c710)\nD 1 (#0dc571)\nL 2 (#5713f0)\nU 1 (#d2c081)";
+        let polygon = Polygon::from_steps(&parse_steps(input, Step::parse).unwrap());
+
+        assert_eq!(
+            polygon.to_svg(),
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-1 -1 4 3\">
+  <path d=\"M0,0 L2,0 L2,1 L0,1 L0,0 Z\" fill=\"#888888\" fill-opacity=\"0.5\" stroke=\"none\"/>
+  <line x1=\"0\" y1=\"0\" x2=\"2\" y2=\"0\" stroke=\"#70c710\" stroke-width=\"1\" stroke-linecap=\"square\"/>
+  <line x1=\"2\" y1=\"0\" x2=\"2\" y2=\"1\" stroke=\"#0dc571\" stroke-width=\"1\" stroke-linecap=\"square\"/>
+  <line x1=\"2\" y1=\"1\" x2=\"0\" y2=\"1\" stroke=\"#5713f0\" stroke-width=\"1\" stroke-linecap=\"square\"/>
+  <line x1=\"0\" y1=\"1\" x2=\"0\" y2=\"0\" stroke=\"#d2c081\" stroke-width=\"1\" stroke-linecap=\"square\"/>
+</svg>
+"
+        );
+
+        let svg = get_polygon("test.txt", Step::parse_2).to_svg();
+        assert!(svg.contains("viewBox=\"-2372 -2372 1191072 1191072\""));
+        assert!(svg.contains("stroke=\"#70c710\" stroke-width=\"2372\""));
+    }
 }
@@ -1,30 +1,47 @@
-use std::{collections::HashMap, fs};
+#![allow(clippy::needless_return)]
 
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    env, fmt, fs,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum Order {
     Less,
     Greater,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
-enum Category {
-    X,
-    M,
-    A,
-    S,
-}
+// The name of a rating, like the "x" in `x>10:A`. Any category the
+// workflows and parts mention is valid.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+struct Category(String);
 
 impl Category {
-    fn parse(s: &str) -> Self {
-        match s {
-            "x" => Category::X,
-            "m" => Category::M,
-            "a" => Category::A,
-            "s" => Category::S,
-            _ => panic!("Unknown category: {}", s),
+    fn parse(s: &str) -> Result<Self, ParseError> {
+        if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(ParseError::InvalidCategory(s.to_string()));
         }
+
+        return Ok(Category(s.to_string()));
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.0);
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+enum ParseError {
+    InvalidCategory(String),
+    InvalidPart(String),
+    InvalidRule(String),
+    InvalidThreshold(String),
+    MalformedWorkflow(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
 enum Destination {
     Accepted,
     Rejected,
@@ -41,6 +58,17 @@ impl Destination {
     }
 }
 
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Destination::Accepted => write!(f, "A"),
+            Destination::Rejected => write!(f, "R"),
+            Destination::Workflow(name) => write!(f, "{}", name),
+        };
+    }
+}
+
+#[derive(Debug)]
 struct Rule {
     category: Category,
     order: Order,
@@ -48,69 +76,76 @@ struct Rule {
     destination: Destination,
 }
 
+fn compare(value: i64, order: &Order, threshold: i64) -> bool {
+    return match order {
+        Order::Less => value < threshold,
+        Order::Greater => value > threshold,
+    };
+}
+
 impl Rule {
-    fn parse(s: &str) -> Self {
-        let order;
-        let mut order_split;
-        if s.contains("<") {
-            order = Order::Less;
-            order_split = s.split("<");
-        } else if s.contains(">") {
-            order = Order::Greater;
-            order_split = s.split(">");
-        } else {
-            panic!("Cannot parse rule: {}", s);
+    fn parse(s: &str) -> Result<Self, ParseError> {
+        let invalid = || ParseError::InvalidRule(s.to_string());
+        let (condition, destination) = s.split_once(":").ok_or_else(invalid)?;
+        if destination.is_empty() {
+            return Err(invalid());
         }
 
-        let category = Category::parse(order_split.next().unwrap());
+        let (order, (category, threshold)) = if let Some(split) = condition.split_once("<") {
+            (Order::Less, split)
+        } else if let Some(split) = condition.split_once(">") {
+            (Order::Greater, split)
+        } else {
+            return Err(invalid());
+        };
 
-        let mut colon_split = order_split.next().unwrap().split(":");
-        let threshold = colon_split.next().unwrap().parse().unwrap();
-        let destination = Destination::parse(colon_split.next().unwrap());
+        let category = Category::parse(category)?;
+        let threshold = threshold
+            .parse()
+            .map_err(|_| ParseError::InvalidThreshold(threshold.to_string()))?;
+        let destination = Destination::parse(destination);
 
-        Self {
+        Ok(Self {
             category,
             order,
             threshold,
             destination,
-        }
+        })
     }
 
+    // A part without a rating for the category never satisfies the rule.
     fn satisfies(&self, part: &Part) -> bool {
-        let value = match self.category {
-            Category::X => part.x,
-            Category::M => part.m,
-            Category::A => part.a,
-            Category::S => part.s,
-        };
+        return part
+            .get(&self.category)
+            .is_some_and(|value| compare(value, &self.order, self.threshold));
+    }
 
-        match self.order {
-            Order::Less => value < self.threshold,
-            Order::Greater => value > self.threshold,
-        }
+    // The rule separates the values <= split_point from the ones above it.
+    fn split_point(&self) -> i64 {
+        return match self.order {
+            Order::Less => self.threshold - 1,
+            Order::Greater => self.threshold,
+        };
     }
 
     fn split_interval(&self, part: &IntervalPart) -> (Option<IntervalPart>, Option<IntervalPart>) {
-        match self.order {
-            Order::Less => {
-                let (lower, higher) = part[&self.category].clone().split(self.threshold - 1);
+        let (lower, higher) = part.split(&self.category, self.split_point());
 
-                let accepted = lower.map(|lower| new_interval_part(part, lower, &self.category));
-                let rejected: Option<HashMap<Category, Interval>> =
-                    higher.map(|higher| new_interval_part(part, higher, &self.category));
-
-                return (rejected, accepted);
-            }
-            Order::Greater => {
-                let (lower, higher) = part[&self.category].clone().split(self.threshold);
+        return match self.order {
+            Order::Less => (higher, lower),
+            Order::Greater => (lower, higher),
+        };
+    }
+}
 
-                let accepted = higher.map(|higher| new_interval_part(part, higher, &self.category));
-                let rejected: Option<HashMap<Category, Interval>> =
-                    lower.map(|lower| new_interval_part(part, lower, &self.category));
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let order = match self.order {
+            Order::Less => "<",
+            Order::Greater => ">",
+        };
 
-                return (rejected, accepted);
-            }
-        }
+        return write!(f, "{}{}{}", self.category, order, self.threshold);
     }
 }
 
@@ -120,30 +155,29 @@ struct Workflow {
 }
 
 impl Workflow {
-    fn parse(s: &str) -> Self {
+    fn parse(s: &str) -> Result<Self, ParseError> {
         let split = s.split(",").collect::<Vec<_>>();
+        // Splitting always gives at least one piece, the final destination.
         let (last, rules) = split.split_last().unwrap();
-
-        Self {
-            rules: rules.iter().map(|rule| Rule::parse(&rule)).collect(),
-            final_destination: Destination::parse(&last),
+        if last.is_empty() || last.contains([':', '<', '>']) {
+            return Err(ParseError::MalformedWorkflow(s.to_string()));
         }
-    }
 
-    fn process_part<'a>(&'a self, part: &Part) -> &'a Destination {
-        for rule in &self.rules {
-            if rule.satisfies(&part) {
-                return &rule.destination;
-            }
-        }
+        Ok(Self {
+            rules: rules
+                .iter()
+                .map(|rule| Rule::parse(rule))
+                .collect::<Result<_, _>>()?,
+            final_destination: Destination::parse(last),
+        })
+    }
 
-        &self.final_destination
+    // None means no rule matched and the part went to the final destination.
+    fn fired_rule(&self, part: &Part) -> Option<&Rule> {
+        return self.rules.iter().find(|rule| rule.satisfies(part));
     }
 
-    fn process_interval<'a>(
-        &'a self,
-        mut part: IntervalPart,
-    ) -> Vec<(IntervalPart, &'a Destination)> {
+    fn process_interval(&self, mut part: IntervalPart) -> Vec<(IntervalPart, &Destination)> {
         let mut result = Vec::new();
         let mut all_satisfied = false;
 
@@ -178,7 +212,7 @@ struct WorkflowMap {
 }
 
 impl WorkflowMap {
-    fn parse(s: &str) -> Self {
+    fn parse(s: &str) -> Result<Self, ParseError> {
         let mut result = WorkflowMap {
             map: HashMap::new(),
             accepted: Vec::new(),
@@ -187,94 +221,206 @@ impl WorkflowMap {
         };
 
         for line in s.lines() {
-            let mut split = line.split("{");
-            let name = split.next().unwrap();
-            let workflow = split.next().unwrap().strip_suffix("}").unwrap();
+            let (name, workflow) = line
+                .strip_suffix("}")
+                .and_then(|line| line.split_once("{"))
+                .filter(|(name, _)| !name.is_empty())
+                .ok_or_else(|| ParseError::MalformedWorkflow(line.to_string()))?;
 
             result
                 .map
-                .insert(name.to_string(), Workflow::parse(workflow));
+                .insert(name.to_string(), Workflow::parse(workflow)?);
         }
 
-        return result;
+        return Ok(result);
     }
 
-    fn process_part(&mut self, part: Part) {
-        let mut workflow = &self.map["in"];
+    // Goes through trace, so a part stuck in a cycle or sent to a missing
+    // workflow is an error instead of looping or panicking.
+    fn process_part(&mut self, part: Part) -> Result<(), CompileError> {
+        let steps = self.trace(&part)?;
+        let accepted = matches!(
+            steps.last().map(|step| step.destination),
+            Some(Destination::Accepted)
+        );
 
-        while let Destination::Workflow(workflow_name) = workflow.process_part(&part) {
-            workflow = &self.map[workflow_name];
+        if accepted {
+            self.accepted.push(part);
+        } else {
+            self.rejected.push(part);
         }
 
-        match workflow.process_part(&part) {
-            Destination::Accepted => self.accepted.push(part),
-            Destination::Rejected => self.rejected.push(part),
-            Destination::Workflow(_) => (),
-        };
+        return Ok(());
+    }
+
+    fn trace(&self, part: &Part) -> Result<Vec<TraceStep<'_>>, CompileError> {
+        let mut steps: Vec<TraceStep> = Vec::new();
+        let mut name = "in";
+
+        loop {
+            if steps.iter().any(|step| step.workflow == name) {
+                let mut cycle: Vec<String> =
+                    steps.iter().map(|step| step.workflow.to_string()).collect();
+                cycle.push(name.to_string());
+                return Err(CompileError::Cycle(cycle));
+            }
+
+            let workflow = self
+                .map
+                .get(name)
+                .ok_or(CompileError::UnknownWorkflow(name.to_string()))?;
+            let rule = workflow.fired_rule(part);
+            let destination = match rule {
+                Some(rule) => &rule.destination,
+                None => &workflow.final_destination,
+            };
+
+            steps.push(TraceStep {
+                workflow: name,
+                rule,
+                destination,
+            });
+
+            match destination {
+                Destination::Workflow(next) => name = next,
+                _ => return Ok(steps),
+            }
+        }
+    }
+
+    fn process_interval(&mut self, part: IntervalPart) -> Result<(), CompileError> {
+        let boxes = self.accepted_boxes(&part)?;
+        self.accepted_intervals.extend(boxes);
+
+        return Ok(());
     }
 
-    fn process_interval(&mut self, part: IntervalPart) {
-        let mut stack = vec![(part, &self.map["in"])];
+    fn categories(&self) -> BTreeSet<Category> {
+        return self
+            .map
+            .values()
+            .flat_map(|workflow| workflow.rules.iter().map(|rule| rule.category.clone()))
+            .collect();
+    }
+
+    // Every category the workflows use, each over 1..=4000.
+    fn default_bounds(&self) -> IntervalPart {
+        return self
+            .categories()
+            .into_iter()
+            .fold(IntervalPart::new(), |bounds, category| {
+                bounds.with_bound(category, Interval::new(1, 4000))
+            });
+    }
+
+    // The returned boxes are disjoint, since every rule splits its input box
+    // into the part that passes and the part that falls through.
+    // Like compiling, only workflows a box can reach count as missing or
+    // cyclic.
+    fn accepted_boxes(&self, bounds: &IntervalPart) -> Result<Vec<IntervalPart>, CompileError> {
+        if let Some(category) = self
+            .categories()
+            .into_iter()
+            .find(|category| bounds.get(category).is_none())
+        {
+            return Err(CompileError::MissingCategory(category));
+        }
+
+        let mut boxes = Vec::new();
+        // Every box with the workflows it went through, the last one being
+        // where it is now.
+        let mut stack = vec![(bounds.clone(), vec!["in"])];
+
+        while let Some((part, path)) = stack.pop() {
+            let name = *path.last().unwrap();
+            let workflow = self
+                .map
+                .get(name)
+                .ok_or(CompileError::UnknownWorkflow(name.to_string()))?;
 
-        while let Some((part, workflow)) = stack.pop() {
             for (new_part, new_destination) in workflow.process_interval(part) {
                 match new_destination {
-                    Destination::Accepted => self.accepted_intervals.push(new_part),
+                    Destination::Accepted => boxes.push(new_part),
                     Destination::Rejected => (),
-                    Destination::Workflow(name) => stack.push((new_part, &self.map[name])),
+                    Destination::Workflow(next) => {
+                        let mut new_path = path.clone();
+                        new_path.push(next);
+
+                        if path.contains(&next.as_str()) {
+                            let cycle = new_path.iter().map(|name| name.to_string()).collect();
+                            return Err(CompileError::Cycle(cycle));
+                        }
+                        stack.push((new_part, new_path));
+                    }
                 }
             }
         }
+
+        return Ok(boxes);
     }
 
     fn sum_accepted(&self) -> i64 {
-        self.accepted
-            .iter()
-            .map(|part| part.x + part.m + part.a + part.s)
-            .sum()
+        self.accepted.iter().map(|part| part.rating()).sum()
     }
 
-    fn combinations(&self) -> i64 {
+    fn combinations(&self) -> i128 {
         self.accepted_intervals
             .iter()
-            .map(|part| {
-                part[&Category::X].values()
-                    * part[&Category::M].values()
-                    * part[&Category::A].values()
-                    * part[&Category::S].values()
-            })
+            .map(|part| part.volume())
             .sum()
     }
 }
 
+#[derive(Debug)]
+struct TraceStep<'a> {
+    workflow: &'a str,
+    rule: Option<&'a Rule>,
+    destination: &'a Destination,
+}
+
+impl fmt::Display for TraceStep<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self.rule {
+            Some(rule) => write!(f, "{}: {} -> {}", self.workflow, rule, self.destination),
+            None => write!(f, "{}: otherwise -> {}", self.workflow, self.destination),
+        };
+    }
+}
+
 #[derive(Debug)]
 struct Part {
-    x: i64,
-    m: i64,
-    a: i64,
-    s: i64,
+    ratings: BTreeMap<Category, i64>,
 }
 
 impl Part {
-    fn parse_value(s: &str, category_name: &str) -> i64 {
-        s.strip_prefix(category_name).unwrap().parse().unwrap()
+    fn get(&self, category: &Category) -> Option<i64> {
+        return self.ratings.get(category).copied();
     }
 
-    fn parse(s: &str) -> Self {
-        let s = s.strip_prefix("{").unwrap().strip_suffix("}").unwrap();
-        let mut split = s.split(",");
-
-        Self {
-            x: Part::parse_value(split.next().unwrap(), "x="),
-            m: Part::parse_value(split.next().unwrap(), "m="),
-            a: Part::parse_value(split.next().unwrap(), "a="),
-            s: Part::parse_value(split.next().unwrap(), "s="),
+    fn rating(&self) -> i64 {
+        return self.ratings.values().sum();
+    }
+
+    fn parse(s: &str) -> Result<Self, ParseError> {
+        let invalid = || ParseError::InvalidPart(s.to_string());
+        let ratings_list = s
+            .strip_prefix("{")
+            .and_then(|s| s.strip_suffix("}"))
+            .ok_or_else(invalid)?;
+        let mut ratings = BTreeMap::new();
+
+        for rating in ratings_list.split(",") {
+            let (category, value) = rating.split_once("=").ok_or_else(invalid)?;
+            let value = value.parse().map_err(|_| invalid())?;
+            ratings.insert(Category::parse(category)?, value);
         }
+
+        Ok(Self { ratings })
     }
 }
 
-fn parse_parts(s: &str) -> Vec<Part> {
-    s.lines().map(|line| Part::parse(line)).collect()
+fn parse_parts(s: &str) -> Result<Vec<Part>, ParseError> {
+    s.lines().map(Part::parse).collect()
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -308,47 +454,935 @@ impl Interval {
     }
 }
 
-type IntervalPart = HashMap<Category, Interval>;
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}..={}", self.start, self.end);
+    }
+}
 
-fn new_interval_part(part: &IntervalPart, interval: Interval, category: &Category) -> IntervalPart {
-    let mut result = HashMap::new();
+// An axis-aligned box with one closed interval per dimension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HyperRect<K: Ord> {
+    bounds: BTreeMap<K, Interval>,
+}
+
+impl<K: Ord + Clone> HyperRect<K> {
+    fn new() -> Self {
+        return HyperRect {
+            bounds: BTreeMap::new(),
+        };
+    }
 
-    for (key, value) in part {
-        result.insert(key.clone(), value.clone());
+    fn with_bound(mut self, key: K, interval: Interval) -> Self {
+        self.bounds.insert(key, interval);
+        return self;
     }
 
-    result.insert(category.clone(), interval);
+    fn get(&self, key: &K) -> Option<&Interval> {
+        return self.bounds.get(key);
+    }
 
-    return result;
+    // Splits along one dimension into the values <= mid and the values > mid.
+    fn split(&self, key: &K, mid: i64) -> (Option<Self>, Option<Self>) {
+        let (lower, higher) = self.bounds[key].clone().split(mid);
+        let with = |interval: Interval| {
+            let mut result = self.clone();
+            result.bounds.insert(key.clone(), interval);
+            return result;
+        };
+
+        return (lower.map(with), higher.map(with));
+    }
+
+    #[cfg(test)]
+    fn intersects(&self, other: &Self) -> bool {
+        return self
+            .bounds
+            .iter()
+            .all(|(key, interval)| match other.bounds.get(key) {
+                Some(o) => interval.start <= o.end && o.start <= interval.end,
+                None => true,
+            });
+    }
+
+    fn volume(&self) -> i128 {
+        return self
+            .bounds
+            .values()
+            .map(|interval| interval.values() as i128)
+            .product();
+    }
+}
+
+impl<K: Ord + fmt::Display> fmt::Display for HyperRect<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dimensions: Vec<String> = self
+            .bounds
+            .iter()
+            .map(|(key, interval)| format!("{}={}", key, interval))
+            .collect();
+
+        return write!(f, "{{{}}}", dimensions.join(","));
+    }
+}
+
+type IntervalPart = HyperRect<Category>;
+
+#[derive(Debug, PartialEq, Eq)]
+enum CompileError {
+    UnknownWorkflow(String),
+    Cycle(Vec<String>),
+    MissingCategory(Category),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Node {
+    Accept,
+    Reject,
+    Branch {
+        category: Category,
+        order: Order,
+        threshold: i64,
+        pass: usize,
+        fail: usize,
+    },
+}
+
+const ACCEPT: usize = 0;
+const REJECT: usize = 1;
+
+// Workflows flattened into a single tree, following only the rules a part
+// can actually reach from the root.
+#[derive(Debug)]
+struct DecisionTree {
+    nodes: Vec<Node>,
+    root: usize,
+}
+
+impl DecisionTree {
+    fn accepts(&self, part: &Part) -> bool {
+        let mut current = self.root;
+
+        loop {
+            match &self.nodes[current] {
+                Node::Accept => return true,
+                Node::Reject => return false,
+                Node::Branch {
+                    category,
+                    order,
+                    threshold,
+                    pass,
+                    fail,
+                } => {
+                    let value = part.get(category);
+                    current = if value.is_some_and(|value| compare(value, order, *threshold)) {
+                        *pass
+                    } else {
+                        *fail
+                    };
+                }
+            }
+        }
+    }
 }
 
-//167409079868000
+// Everything reachable from a workflow when rules are not pruned.
+struct Reach {
+    workflows: HashSet<String>,
+    split_points: BTreeMap<Category, Vec<i64>>,
+}
+
+impl Reach {
+    fn new(map: &HashMap<String, Workflow>, root: &str) -> Self {
+        let mut workflows = HashSet::new();
+        let mut split_points: BTreeMap<Category, BTreeSet<i64>> = BTreeMap::new();
+        let mut stack = vec![root];
+
+        while let Some(name) = stack.pop() {
+            if !workflows.insert(name.to_string()) {
+                continue;
+            }
+            let Some(workflow) = map.get(name) else {
+                continue;
+            };
+
+            let destinations = workflow
+                .rules
+                .iter()
+                .map(|rule| &rule.destination)
+                .chain([&workflow.final_destination]);
+            for destination in destinations {
+                if let Destination::Workflow(next) = destination {
+                    stack.push(next);
+                }
+            }
+
+            for rule in &workflow.rules {
+                split_points
+                    .entry(rule.category.clone())
+                    .or_default()
+                    .insert(rule.split_point());
+            }
+        }
+
+        return Reach {
+            workflows,
+            split_points: split_points
+                .into_iter()
+                .map(|(category, points)| (category, points.into_iter().collect()))
+                .collect(),
+        };
+    }
+
+    // Boxes with the same cells compile to the same subtree, since every
+    // rule below only asks which side of a split point a bound lies on.
+    fn cells(&self, part: &IntervalPart) -> Vec<(usize, usize)> {
+        return self
+            .split_points
+            .iter()
+            .filter_map(|(category, points)| {
+                let interval = part.get(category)?;
+                let cell = |value: i64| points.partition_point(|point| *point < value);
+
+                return Some((cell(interval.start), cell(interval.end)));
+            })
+            .collect();
+    }
+}
+
+struct Compiler<'a> {
+    map: &'a HashMap<String, Workflow>,
+    nodes: Vec<Node>,
+    live_rules: HashSet<(String, usize)>,
+    stack: Vec<String>,
+    reach: HashMap<String, Reach>,
+    compiled: HashMap<(String, Vec<(usize, usize)>), usize>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(map: &'a HashMap<String, Workflow>) -> Self {
+        return Compiler {
+            map,
+            nodes: vec![Node::Accept, Node::Reject],
+            live_rules: HashSet::new(),
+            stack: Vec::new(),
+            reach: HashMap::new(),
+            compiled: HashMap::new(),
+        };
+    }
+
+    fn compile_destination(
+        &mut self,
+        destination: &Destination,
+        part: IntervalPart,
+    ) -> Result<usize, CompileError> {
+        return match destination {
+            Destination::Accepted => Ok(ACCEPT),
+            Destination::Rejected => Ok(REJECT),
+            Destination::Workflow(name) => self.compile_workflow(name, part),
+        };
+    }
+
+    fn compile_workflow(&mut self, name: &str, part: IntervalPart) -> Result<usize, CompileError> {
+        if self.stack.iter().any(|visited| visited == name) {
+            let mut cycle = self.stack.clone();
+            cycle.push(name.to_string());
+            return Err(CompileError::Cycle(cycle));
+        }
+
+        let map = self.map;
+        let reach = self
+            .reach
+            .entry(name.to_string())
+            .or_insert_with(|| Reach::new(map, name));
+        let key = (name.to_string(), reach.cells(&part));
+
+        // A cached subtree is only reused when it cannot lead back into the
+        // current stack, so cycles are still found on every path.
+        let closes_cycle = self
+            .stack
+            .iter()
+            .any(|visited| reach.workflows.contains(visited));
+        if !closes_cycle {
+            if let Some(node) = self.compiled.get(&key) {
+                return Ok(*node);
+            }
+        }
+
+        self.stack.push(name.to_string());
+        let node = self.compile_rule(name, 0, part);
+        self.stack.pop();
+
+        let node = node?;
+        self.compiled.insert(key, node);
+        return Ok(node);
+    }
+
+    fn compile_rule(
+        &mut self,
+        name: &str,
+        index: usize,
+        part: IntervalPart,
+    ) -> Result<usize, CompileError> {
+        let map = self.map;
+        let workflow = map
+            .get(name)
+            .ok_or(CompileError::UnknownWorkflow(name.to_string()))?;
+
+        if index == workflow.rules.len() {
+            self.live_rules.insert((name.to_string(), index));
+            return self.compile_destination(&workflow.final_destination, part);
+        }
+
+        let rule = &workflow.rules[index];
+        let (failed, passed) = rule.split_interval(&part);
+        let passed = match passed {
+            Some(passed) => passed,
+            None => return self.compile_rule(name, index + 1, failed.unwrap()),
+        };
+
+        self.live_rules.insert((name.to_string(), index));
+        let pass = self.compile_destination(&rule.destination, passed)?;
+        let fail = match failed {
+            Some(failed) => self.compile_rule(name, index + 1, failed)?,
+            None => return Ok(pass),
+        };
+
+        if pass == fail {
+            return Ok(pass);
+        }
+
+        self.nodes.push(Node::Branch {
+            category: rule.category.clone(),
+            order: rule.order,
+            threshold: rule.threshold,
+            pass,
+            fail,
+        });
+
+        return Ok(self.nodes.len() - 1);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Analysis {
+    unreachable_workflows: Vec<String>,
+    unreachable_rules: Vec<(String, usize)>,
+    always_accept: Vec<String>,
+    always_reject: Vec<String>,
+    broken_workflows: Vec<(String, CompileError)>,
+}
+
+impl WorkflowMap {
+    fn compile(&self, root: &str) -> Result<DecisionTree, CompileError> {
+        let mut compiler = Compiler::new(&self.map);
+        let root = compiler.compile_workflow(root, self.default_bounds())?;
+
+        return Ok(DecisionTree {
+            nodes: compiler.nodes,
+            root,
+        });
+    }
+
+    // Rule indexes equal to the number of rules refer to the final
+    // destination of the workflow. Only a broken root fails the analysis,
+    // other workflows that do not compile are listed as broken.
+    fn analyze(&self, root: &str) -> Result<Analysis, CompileError> {
+        let mut compiler = Compiler::new(&self.map);
+        compiler.compile_workflow(root, self.default_bounds())?;
+        let mut standalone = Compiler::new(&self.map);
+
+        let mut names: Vec<&String> = self.map.keys().collect();
+        names.sort();
+
+        let mut analysis = Analysis {
+            unreachable_workflows: Vec::new(),
+            unreachable_rules: Vec::new(),
+            always_accept: Vec::new(),
+            always_reject: Vec::new(),
+            broken_workflows: Vec::new(),
+        };
+
+        for name in names {
+            let workflow = &self.map[name];
+            if !compiler.live_rules.iter().any(|(live, _)| live == name) {
+                analysis.unreachable_workflows.push(name.clone());
+            } else {
+                for index in 0..=workflow.rules.len() {
+                    if !compiler.live_rules.contains(&(name.clone(), index)) {
+                        analysis.unreachable_rules.push((name.clone(), index));
+                    }
+                }
+            }
+
+            match standalone.compile_workflow(name, self.default_bounds()) {
+                Ok(ACCEPT) => analysis.always_accept.push(name.clone()),
+                Ok(REJECT) => analysis.always_reject.push(name.clone()),
+                Ok(_) => (),
+                Err(error) => analysis.broken_workflows.push((name.clone(), error)),
+            }
+        }
+
+        return Ok(analysis);
+    }
+}
 
 fn main() {
     let input = fs::read_to_string("input.txt").unwrap();
 
     let mut split = input.split("\n\n");
-    let mut wmap = WorkflowMap::parse(split.next().unwrap());
-    let parts = parse_parts(split.next().unwrap());
+    let mut wmap = WorkflowMap::parse(split.next().unwrap()).unwrap();
+    let parts = parse_parts(split.next().unwrap()).unwrap();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "trace") {
+        for literal in &args[1..] {
+            println!("{}", literal);
+            let part = match Part::parse(literal) {
+                Ok(part) => part,
+                Err(error) => {
+                    println!("  {:?}", error);
+                    continue;
+                }
+            };
+            match wmap.trace(&part) {
+                Ok(steps) => {
+                    for step in steps {
+                        println!("  {}", step);
+                    }
+                }
+                Err(error) => println!("  {:?}", error),
+            }
+        }
+        return;
+    }
+
+    let tree = wmap.compile("in").unwrap();
+    let parts_sum: i64 = parts
+        .iter()
+        .filter(|part| tree.accepts(part))
+        .map(|part| part.rating())
+        .sum();
+    println!("{}", parts_sum);
 
     for part in parts {
-        wmap.process_part(part);
+        wmap.process_part(part).unwrap();
     }
+    assert_eq!(wmap.sum_accepted(), parts_sum);
 
-    let mut interval_part = IntervalPart::new();
-    interval_part.insert(Category::X, Interval::new(1, 4000));
-    interval_part.insert(Category::M, Interval::new(1, 4000));
-    interval_part.insert(Category::A, Interval::new(1, 4000));
-    interval_part.insert(Category::S, Interval::new(1, 4000));
-
-    wmap.process_interval(interval_part);
+    wmap.process_interval(wmap.default_bounds()).unwrap();
 
     println!("{}", wmap.combinations());
+
+    if args.iter().any(|arg| arg == "--boxes") {
+        for accepted in &wmap.accepted_intervals {
+            println!("{} {}", accepted, accepted.volume());
+        }
+    }
+
+    if args.iter().any(|arg| arg == "--analyze") {
+        let analysis = wmap.analyze("in").unwrap();
+        println!("decision tree nodes: {}", tree.nodes.len());
+        println!(
+            "unreachable workflows: {:?}",
+            analysis.unreachable_workflows
+        );
+        println!("unreachable rules: {:?}", analysis.unreachable_rules);
+        println!("always accept: {:?}", analysis.always_accept);
+        println!("always reject: {:?}", analysis.always_reject);
+        println!("broken workflows: {:?}", analysis.broken_workflows);
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Interval;
+    use std::fs;
+
+    use crate::{
+        parse_parts, Category, CompileError, Destination, HyperRect, Interval, IntervalPart,
+        ParseError, Part, WorkflowMap,
+    };
+
+    fn category(name: &str) -> Category {
+        return Category::parse(name).unwrap();
+    }
+
+    fn xmas(x: i64, m: i64, a: i64, s: i64) -> Part {
+        return Part {
+            ratings: [("x", x), ("m", m), ("a", a), ("s", s)]
+                .into_iter()
+                .map(|(name, value)| (category(name), value))
+                .collect(),
+        };
+    }
+
+    fn check_tree_matches_interpreter(file: &str) {
+        let input = fs::read_to_string(file).unwrap();
+        let mut split = input.split("\n\n");
+        let mut wmap = WorkflowMap::parse(split.next().unwrap()).unwrap();
+        let parts = parse_parts(split.next().unwrap()).unwrap();
+        let tree = wmap.compile("in").unwrap();
+
+        for part in parts {
+            let accepted = tree.accepts(&part);
+            let before = wmap.accepted.len();
+            wmap.process_part(part).unwrap();
+            assert_eq!(wmap.accepted.len() > before, accepted);
+        }
+    }
+
+    #[test]
+    fn tree_matches_interpreter() {
+        check_tree_matches_interpreter("test.txt");
+        check_tree_matches_interpreter("input.txt");
+    }
+
+    #[test]
+    fn trace() {
+        let input = fs::read_to_string("test.txt").unwrap();
+        let wmap = WorkflowMap::parse(input.split("\n\n").next().unwrap()).unwrap();
+
+        let steps = wmap
+            .trace(&Part::parse("{x=787,m=2655,a=1222,s=2876}").unwrap())
+            .unwrap();
+        let lines: Vec<String> = steps.iter().map(|step| step.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "in: otherwise -> qqz",
+                "qqz: s>2770 -> qs",
+                "qs: otherwise -> lnx",
+                "lnx: m>1548 -> A"
+            ]
+        );
+
+        let steps = wmap
+            .trace(&Part::parse("{x=1679,m=44,a=2067,s=496}").unwrap())
+            .unwrap();
+        let workflows: Vec<&str> = steps.iter().map(|step| step.workflow).collect();
+        assert_eq!(workflows, vec!["in", "px", "rfg", "gd"]);
+        assert_eq!(steps.last().unwrap().destination, &Destination::Rejected);
+    }
+
+    #[test]
+    fn trace_into_unknown_workflow() {
+        let wmap = WorkflowMap::parse("in{x<10:a,R}\na{m>5:zz,A}").unwrap();
+
+        assert_eq!(
+            wmap.trace(&Part::parse("{x=5,m=6,a=1,s=1}").unwrap())
+                .unwrap_err(),
+            CompileError::UnknownWorkflow("zz".to_string())
+        );
+        assert_eq!(
+            wmap.trace(&Part::parse("{x=5,m=5,a=1,s=1}").unwrap())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn trace_around_cycle() {
+        let wmap = WorkflowMap::parse("in{x<10:a,R}\na{m>5:b,A}\nb{a<3:a,R}").unwrap();
+
+        assert_eq!(
+            wmap.trace(&Part::parse("{x=5,m=6,a=1,s=1}").unwrap())
+                .unwrap_err(),
+            CompileError::Cycle(vec![
+                "in".to_string(),
+                "a".to_string(),
+                "b".to_string(),
+                "a".to_string()
+            ])
+        );
+        assert_eq!(
+            wmap.trace(&Part::parse("{x=5,m=6,a=3,s=1}").unwrap())
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn process_part_errors() {
+        let mut wmap = WorkflowMap::parse("in{x<10:a,R}\na{m>5:b,A}\nb{a<3:a,zz}").unwrap();
+
+        assert_eq!(
+            wmap.process_part(Part::parse("{x=5,m=6,a=1,s=1}").unwrap()),
+            Err(CompileError::Cycle(vec![
+                "in".to_string(),
+                "a".to_string(),
+                "b".to_string(),
+                "a".to_string()
+            ]))
+        );
+        assert_eq!(
+            wmap.process_part(Part::parse("{x=5,m=6,a=3,s=1}").unwrap()),
+            Err(CompileError::UnknownWorkflow("zz".to_string()))
+        );
+        assert!(wmap.accepted.is_empty() && wmap.rejected.is_empty());
+
+        assert_eq!(
+            wmap.process_part(Part::parse("{x=5,m=5,a=1,s=1}").unwrap()),
+            Ok(())
+        );
+        assert_eq!(
+            wmap.process_part(Part::parse("{x=10,m=5,a=1,s=1}").unwrap()),
+            Ok(())
+        );
+        assert_eq!((wmap.accepted.len(), wmap.rejected.len()), (1, 1));
+    }
+
+    #[test]
+    fn hyper_rect_split() {
+        let rect = HyperRect::new()
+            .with_bound("a", Interval::new(1, 10))
+            .with_bound("b", Interval::new(1, 3));
+
+        let (lower, higher) = rect.split(&"a", 4);
+        assert_eq!(
+            lower.as_ref().unwrap().get(&"a"),
+            Some(&Interval::new(1, 4))
+        );
+        assert_eq!(
+            higher.as_ref().unwrap().get(&"a"),
+            Some(&Interval::new(5, 10))
+        );
+        assert_eq!(
+            lower.as_ref().unwrap().get(&"b"),
+            Some(&Interval::new(1, 3))
+        );
+        assert_eq!(rect.volume(), 30);
+        assert_eq!(lower.unwrap().volume() + higher.unwrap().volume(), 30);
+    }
+
+    #[test]
+    fn accepted_boxes() {
+        let input = fs::read_to_string("test.txt").unwrap();
+        let wmap = WorkflowMap::parse(input.split("\n\n").next().unwrap()).unwrap();
+        let boxes = wmap.accepted_boxes(&wmap.default_bounds()).unwrap();
+
+        assert_eq!(
+            boxes.iter().map(|b| b.volume()).sum::<i128>(),
+            167409079868000
+        );
+        for (i, a) in boxes.iter().enumerate() {
+            for b in &boxes[i + 1..] {
+                assert!(!a.intersects(b));
+            }
+        }
+    }
+
+    #[test]
+    fn accepted_boxes_with_custom_bounds() {
+        let input = fs::read_to_string("test.txt").unwrap();
+        let wmap = WorkflowMap::parse(input.split("\n\n").next().unwrap()).unwrap();
+        let tree = wmap.compile("in").unwrap();
+
+        let bounds = IntervalPart::new()
+            .with_bound(category("x"), Interval::new(1400, 1430))
+            .with_bound(category("m"), Interval::new(830, 850))
+            .with_bound(category("a"), Interval::new(1990, 2010))
+            .with_bound(category("s"), Interval::new(1340, 1360));
+        let boxes = wmap.accepted_boxes(&bounds).unwrap();
+
+        let mut expected = 0;
+        for x in 1400..=1430 {
+            for m in 830..=850 {
+                for a in 1990..=2010 {
+                    for s in 1340..=1360 {
+                        if tree.accepts(&xmas(x, m, a, s)) {
+                            expected += 1;
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(boxes.iter().map(|b| b.volume()).sum::<i128>(), expected);
+    }
+
+    #[test]
+    fn accepted_boxes_over_used_categories() {
+        let wmap = WorkflowMap::parse("in{x<10:a,R}\na{m>5:A,R}").unwrap();
+        assert_eq!(
+            wmap.categories().into_iter().collect::<Vec<_>>(),
+            vec![category("m"), category("x")]
+        );
+
+        let bounds = IntervalPart::new()
+            .with_bound(category("x"), Interval::new(1, 100))
+            .with_bound(category("m"), Interval::new(1, 10));
+        let boxes = wmap.accepted_boxes(&bounds).unwrap();
+        assert_eq!(boxes.iter().map(|b| b.volume()).sum::<i128>(), 9 * 5);
+
+        let bounds = IntervalPart::new().with_bound(category("x"), Interval::new(1, 100));
+        assert_eq!(
+            wmap.accepted_boxes(&bounds).unwrap_err(),
+            CompileError::MissingCategory(category("m"))
+        );
+    }
+
+    #[test]
+    fn categories_are_data() {
+        let wmap = WorkflowMap::parse("in{len<10:heavy,R}\nheavy{kg>5:A,R}").unwrap();
+        assert_eq!(
+            wmap.default_bounds().to_string(),
+            "{kg=1..=4000,len=1..=4000}"
+        );
+
+        let bounds = IntervalPart::new()
+            .with_bound(category("len"), Interval::new(1, 20))
+            .with_bound(category("kg"), Interval::new(1, 10));
+        let boxes = wmap.accepted_boxes(&bounds).unwrap();
+        assert_eq!(boxes.iter().map(|b| b.volume()).sum::<i128>(), 9 * 5);
+
+        let tree = wmap.compile("in").unwrap();
+        assert!(tree.accepts(&Part::parse("{len=3,kg=6}").unwrap()));
+        assert!(!tree.accepts(&Part::parse("{len=3,kg=5}").unwrap()));
+        // Without a weight, kg>5 can not hold.
+        assert!(!tree.accepts(&Part::parse("{len=3}").unwrap()));
+        assert_eq!(Part::parse("{len=3,kg=6}").unwrap().rating(), 9);
+
+        assert_eq!(
+            WorkflowMap::parse("in{x<10:A,R}\na{4<x:A,R}")
+                .err()
+                .unwrap(),
+            ParseError::InvalidCategory("4".to_string())
+        );
+        assert_eq!(
+            Part::parse("{x=1,=2}").unwrap_err(),
+            ParseError::InvalidCategory("".to_string())
+        );
+        assert_eq!(
+            Part::parse("{x=1,m=}").unwrap_err(),
+            ParseError::InvalidPart("{x=1,m=}".to_string())
+        );
+        assert_eq!(
+            Part::parse("x=1").unwrap_err(),
+            ParseError::InvalidPart("x=1".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_malformed_rules() {
+        let error = |s: &str| WorkflowMap::parse(s).err().unwrap();
+
+        assert_eq!(
+            error("in{x=10:A,R}"),
+            ParseError::InvalidRule("x=10:A".to_string())
+        );
+        assert_eq!(
+            error("in{x<10,R}"),
+            ParseError::InvalidRule("x<10".to_string())
+        );
+        assert_eq!(
+            error("in{x<10:,R}"),
+            ParseError::InvalidRule("x<10:".to_string())
+        );
+        assert_eq!(
+            error("in{x<ten:A,R}"),
+            ParseError::InvalidThreshold("ten".to_string())
+        );
+        assert_eq!(
+            error("in{x<:A,R}"),
+            ParseError::InvalidThreshold("".to_string())
+        );
+        assert_eq!(
+            error("in{x<10:A,m>5:R}"),
+            ParseError::MalformedWorkflow("x<10:A,m>5:R".to_string())
+        );
+        assert_eq!(
+            error("in{x<10:A,R}\na{x>3:A,R"),
+            ParseError::MalformedWorkflow("a{x>3:A,R".to_string())
+        );
+        assert_eq!(
+            error("{x<10:A,R}"),
+            ParseError::MalformedWorkflow("{x<10:A,R}".to_string())
+        );
+    }
+
+    #[test]
+    fn accepted_boxes_report_broken_workflows() {
+        let wmap = WorkflowMap::parse("in{x<10:a,R}\na{m>5:zz,A}").unwrap();
+        assert_eq!(
+            wmap.accepted_boxes(&wmap.default_bounds()).unwrap_err(),
+            CompileError::UnknownWorkflow("zz".to_string())
+        );
+
+        let wmap = WorkflowMap::parse("in{x<10:a,R}\na{m>5:b,A}\nb{a<3:a,R}").unwrap();
+        assert_eq!(
+            wmap.accepted_boxes(&wmap.default_bounds()).unwrap_err(),
+            CompileError::Cycle(vec![
+                "in".to_string(),
+                "a".to_string(),
+                "b".to_string(),
+                "a".to_string()
+            ])
+        );
+
+        let mut wmap = WorkflowMap::parse("in{x<10:a,R}\na{x>20:in,A}").unwrap();
+        wmap.process_interval(wmap.default_bounds()).unwrap();
+        assert_eq!(wmap.combinations(), 9);
+
+        let mut wmap = WorkflowMap::parse("out{x<10:A,R}").unwrap();
+        assert_eq!(
+            wmap.process_interval(wmap.default_bounds()).unwrap_err(),
+            CompileError::UnknownWorkflow("in".to_string())
+        );
+    }
+
+    #[test]
+    fn detects_cycle() {
+        let wmap = WorkflowMap::parse("in{x<10:a,R}\na{x<5:in,A}").unwrap();
+
+        assert_eq!(
+            wmap.compile("in").unwrap_err(),
+            CompileError::Cycle(vec!["in".to_string(), "a".to_string(), "in".to_string()])
+        );
+    }
+
+    #[test]
+    fn cycle_on_dead_branch_is_ignored() {
+        let wmap = WorkflowMap::parse("in{x<10:a,R}\na{x>20:in,A}").unwrap();
+        let tree = wmap.compile("in").unwrap();
+
+        assert!(tree.accepts(&Part::parse("{x=5,m=1,a=1,s=1}").unwrap()));
+        assert!(!tree.accepts(&Part::parse("{x=50,m=1,a=1,s=1}").unwrap()));
+    }
+
+    #[test]
+    fn detects_unknown_workflow() {
+        let wmap = WorkflowMap::parse("in{x<10:b,R}").unwrap();
+
+        assert_eq!(
+            wmap.compile("in").unwrap_err(),
+            CompileError::UnknownWorkflow("b".to_string())
+        );
+    }
+
+    #[test]
+    fn analyze() {
+        let wmap =
+            WorkflowMap::parse("in{x<10:a,x<5:R,s>100:b,R}\na{m>0:A,R}\nb{a<3000:R,R}\nc{x>1:A,A}")
+                .unwrap();
+        let analysis = wmap.analyze("in").unwrap();
+
+        assert_eq!(analysis.unreachable_workflows, vec!["c".to_string()]);
+        assert_eq!(
+            analysis.unreachable_rules,
+            vec![("a".to_string(), 1), ("in".to_string(), 1)]
+        );
+        assert_eq!(
+            analysis.always_accept,
+            vec!["a".to_string(), "c".to_string()]
+        );
+        assert_eq!(analysis.always_reject, vec!["b".to_string()]);
+        assert!(analysis.broken_workflows.is_empty());
+    }
+
+    #[test]
+    fn analyze_with_broken_dead_workflows() {
+        let wmap = WorkflowMap::parse("in{x<10:A,R}\nd{x<5:zz,A}\ne{m>5:f,R}\nf{a<3:e,A}").unwrap();
+        let analysis = wmap.analyze("in").unwrap();
+
+        assert_eq!(
+            analysis.unreachable_workflows,
+            vec!["d".to_string(), "e".to_string(), "f".to_string()]
+        );
+        assert!(analysis.always_accept.is_empty());
+        assert!(analysis.always_reject.is_empty());
+        assert_eq!(
+            analysis.broken_workflows,
+            vec![
+                (
+                    "d".to_string(),
+                    CompileError::UnknownWorkflow("zz".to_string())
+                ),
+                (
+                    "e".to_string(),
+                    CompileError::Cycle(vec!["e".to_string(), "f".to_string(), "e".to_string()])
+                ),
+                (
+                    "f".to_string(),
+                    CompileError::Cycle(vec!["f".to_string(), "e".to_string(), "f".to_string()])
+                ),
+            ]
+        );
+
+        let wmap = WorkflowMap::parse("in{x<10:zz,R}").unwrap();
+        assert_eq!(
+            wmap.analyze("in").unwrap_err(),
+            CompileError::UnknownWorkflow("zz".to_string())
+        );
+    }
+
+    #[test]
+    fn shared_workflows_are_compiled_once() {
+        // Both rules of every workflow lead to the next one, so following
+        // each path separately would visit the last workflow 2^60 times.
+        let categories = ["x", "m", "a", "s"];
+        let mut workflows = Vec::new();
+        for level in 0..60 {
+            let name = if level == 0 {
+                "in".to_string()
+            } else {
+                format!("w{}", level)
+            };
+            workflows.push(format!(
+                "{}{{{}<2000:w{},w{}}}",
+                name,
+                categories[level % 4],
+                level + 1,
+                level + 1
+            ));
+        }
+        workflows.push("w60{x<1000:A,s>3000:A,R}".to_string());
+        let wmap = WorkflowMap::parse(&workflows.join("\n")).unwrap();
+
+        let tree = wmap.compile("in").unwrap();
+        assert!(tree.nodes.len() < 60 * 16);
+        assert!(tree.accepts(&Part::parse("{x=999,m=1,a=1,s=1}").unwrap()));
+        assert!(tree.accepts(&Part::parse("{x=3000,m=1,a=4000,s=3001}").unwrap()));
+        assert!(!tree.accepts(&Part::parse("{x=1000,m=1,a=1,s=3000}").unwrap()));
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture`"]
+    fn bench_decision_tree() {
+        let input = fs::read_to_string("input.txt").unwrap();
+        let wmap = WorkflowMap::parse(input.split("\n\n").next().unwrap()).unwrap();
+        let tree = wmap.compile("in").unwrap();
+
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            return (state % 4000) as i64 + 1;
+        };
+        let parts: Vec<Part> = (0..1_000_000)
+            .map(|_| xmas(next(), next(), next(), next()))
+            .collect();
+
+        let start = std::time::Instant::now();
+        let tree_accepted = parts.iter().filter(|part| tree.accepts(part)).count();
+        let tree_time = start.elapsed();
+
+        let mut wmap = wmap;
+        let start = std::time::Instant::now();
+        for part in parts {
+            wmap.process_part(part).unwrap();
+        }
+        let interpreter_time = start.elapsed();
+
+        assert_eq!(tree_accepted, wmap.accepted.len());
+        println!(
+            "nodes: {}, tree: {:?}, interpreter: {:?}",
+            tree.nodes.len(),
+            tree_time,
+            interpreter_time
+        );
+    }
 
     #[test]
     fn split_interval() {
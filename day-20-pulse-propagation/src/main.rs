@@ -1,7 +1,8 @@
+#![allow(clippy::needless_return)]
+
 use std::{
-    collections::{HashMap, VecDeque},
-    fs,
-    sync::Arc,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    env, fmt, fs, process,
 };
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -10,6 +11,7 @@ enum Pulse {
     High,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
 struct Signal {
     from: String,
     to: String,
@@ -20,6 +22,51 @@ trait Module {
     fn process(&mut self, signal: Signal) -> Vec<Signal>;
 
     fn add_receiver(&mut self, receiver: &str);
+
+    fn receivers(&self) -> &[String];
+
+    fn kind(&self) -> &'static str;
+
+    // Called once for every module wired to send to this one.
+    fn add_input(&mut self, _sender: &str) {}
+
+    // Only output sinks record the signals they receive.
+    fn received(&self) -> Option<&[Signal]> {
+        return None;
+    }
+
+    // Stateless modules have nothing to save. The saved state must not
+    // contain whitespace, so that it fits on one line of a snapshot.
+    fn save(&self) -> Option<String> {
+        return None;
+    }
+
+    // Checks a saved state without applying it.
+    fn validate(&self, _state: &str) -> Result<(), InvalidState> {
+        return Err(InvalidState);
+    }
+
+    fn restore(&mut self, _state: &str) -> Result<(), InvalidState> {
+        return Err(InvalidState);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct InvalidState;
+
+fn save_pulse(pulse: Pulse) -> char {
+    return match pulse {
+        Pulse::Low => '0',
+        Pulse::High => '1',
+    };
+}
+
+fn restore_pulse(c: char) -> Result<Pulse, InvalidState> {
+    return match c {
+        '0' => Ok(Pulse::Low),
+        '1' => Ok(Pulse::High),
+        _ => Err(InvalidState),
+    };
 }
 
 struct Sender {
@@ -41,7 +88,7 @@ impl Sender {
             .map(|receiver| Signal {
                 from: self.name.clone(),
                 to: receiver.clone(),
-                pulse: pulse,
+                pulse,
             })
             .collect()
     }
@@ -55,12 +102,6 @@ struct Broadcaster {
     sender: Sender,
 }
 
-impl Broadcaster {
-    fn start(&self) -> Vec<Signal> {
-        self.sender.send(Pulse::Low)
-    }
-}
-
 impl Module for Broadcaster {
     fn process(&mut self, signal: Signal) -> Vec<Signal> {
         self.sender.send(signal.pulse)
@@ -69,6 +110,14 @@ impl Module for Broadcaster {
     fn add_receiver(&mut self, receiver: &str) {
         self.sender.add_receiver(receiver);
     }
+
+    fn receivers(&self) -> &[String] {
+        return &self.sender.receivers;
+    }
+
+    fn kind(&self) -> &'static str {
+        return "broadcaster";
+    }
 }
 
 struct FlipFlop {
@@ -83,6 +132,14 @@ impl FlipFlop {
             sender: Sender::new(name),
         }
     }
+
+    fn parse_state(state: &str) -> Result<bool, InvalidState> {
+        return match state {
+            "on" => Ok(true),
+            "off" => Ok(false),
+            _ => Err(InvalidState),
+        };
+    }
 }
 
 impl Module for FlipFlop {
@@ -91,12 +148,7 @@ impl Module for FlipFlop {
             Pulse::Low => {
                 self.on = !self.on;
 
-                let pulse;
-                if self.on {
-                    pulse = Pulse::High
-                } else {
-                    pulse = Pulse::Low
-                }
+                let pulse = if self.on { Pulse::High } else { Pulse::Low };
 
                 self.sender.send(pulse)
             }
@@ -107,6 +159,27 @@ impl Module for FlipFlop {
     fn add_receiver(&mut self, receiver: &str) {
         self.sender.add_receiver(receiver);
     }
+
+    fn receivers(&self) -> &[String] {
+        return &self.sender.receivers;
+    }
+
+    fn kind(&self) -> &'static str {
+        return "flip-flop";
+    }
+
+    fn save(&self) -> Option<String> {
+        return Some(String::from(if self.on { "on" } else { "off" }));
+    }
+
+    fn validate(&self, state: &str) -> Result<(), InvalidState> {
+        return FlipFlop::parse_state(state).map(|_| ());
+    }
+
+    fn restore(&mut self, state: &str) -> Result<(), InvalidState> {
+        self.on = FlipFlop::parse_state(state)?;
+        return Ok(());
+    }
 }
 
 struct Conjunction {
@@ -121,23 +194,45 @@ impl Conjunction {
             sender: Sender::new(name),
         }
     }
+
+    // The saved inputs must be exactly the ones wired to this conjunction.
+    fn parse_state(&self, state: &str) -> Result<HashMap<String, Pulse>, InvalidState> {
+        let mut last_received = HashMap::new();
+        for input in state.split(",").filter(|input| !input.is_empty()) {
+            let (name, pulse) = input.split_once("=").ok_or(InvalidState)?;
+            let mut chars = pulse.chars();
+            let pulse = match (chars.next(), chars.next()) {
+                (Some(c), None) => restore_pulse(c)?,
+                _ => return Err(InvalidState),
+            };
+            last_received.insert(name.to_string(), pulse);
+        }
+
+        if last_received.len() != self.last_received.len()
+            || last_received
+                .keys()
+                .any(|name| !self.last_received.contains_key(name))
+        {
+            return Err(InvalidState);
+        }
+
+        return Ok(last_received);
+    }
 }
 
 impl Module for Conjunction {
     fn process(&mut self, signal: Signal) -> Vec<Signal> {
         self.last_received.insert(signal.from.clone(), signal.pulse);
 
-        let pulse;
-
-        if self
+        let pulse = if self
             .last_received
             .values()
             .all(|pulse| *pulse == Pulse::High)
         {
-            pulse = Pulse::Low;
+            Pulse::Low
         } else {
-            pulse = Pulse::High;
-        }
+            Pulse::High
+        };
 
         self.sender.send(pulse)
     }
@@ -145,142 +240,1598 @@ impl Module for Conjunction {
     fn add_receiver(&mut self, receiver: &str) {
         self.sender.add_receiver(receiver);
     }
+
+    fn receivers(&self) -> &[String] {
+        return &self.sender.receivers;
+    }
+
+    fn kind(&self) -> &'static str {
+        return "conjunction";
+    }
+
+    fn add_input(&mut self, sender: &str) {
+        self.last_received.insert(sender.to_string(), Pulse::Low);
+    }
+
+    // Saved as `input=pulse` pairs sorted by input name.
+    fn save(&self) -> Option<String> {
+        let mut inputs: Vec<_> = self.last_received.iter().collect();
+        inputs.sort_by_key(|(name, _)| *name);
+
+        let inputs: Vec<String> = inputs
+            .iter()
+            .map(|(name, pulse)| format!("{}={}", name, save_pulse(**pulse)))
+            .collect();
+
+        return Some(inputs.join(","));
+    }
+
+    fn validate(&self, state: &str) -> Result<(), InvalidState> {
+        return self.parse_state(state).map(|_| ());
+    }
+
+    fn restore(&mut self, state: &str) -> Result<(), InvalidState> {
+        self.last_received = self.parse_state(state)?;
+        return Ok(());
+    }
 }
 
-enum ModuleType {
-    Broadcaster(Broadcaster),
-    FlipFlop(FlipFlop),
-    Conjunction(Conjunction),
+// Sends the opposite of every pulse it receives.
+struct Inverter {
+    sender: Sender,
 }
 
-struct Modules {
-    map: HashMap<String, ModuleType>,
+impl Inverter {
+    fn new(name: &str) -> Self {
+        Self {
+            sender: Sender::new(name),
+        }
+    }
 }
 
-impl Modules {
-    fn parse(s: &str) -> Self {
-        let mut modules = HashMap::new();
-        let broadcaster = Broadcaster {
-            sender: Sender::new("broadcaster"),
-        };
+impl Module for Inverter {
+    fn process(&mut self, signal: Signal) -> Vec<Signal> {
+        match signal.pulse {
+            Pulse::Low => self.sender.send(Pulse::High),
+            Pulse::High => self.sender.send(Pulse::Low),
+        }
+    }
 
-        modules.insert(
-            String::from("broadcaster"),
-            ModuleType::Broadcaster(broadcaster),
-        );
+    fn add_receiver(&mut self, receiver: &str) {
+        self.sender.add_receiver(receiver);
+    }
 
-        for line in s.lines() {
-            let name = line.split(" -> ").next().unwrap();
+    fn receivers(&self) -> &[String] {
+        return &self.sender.receivers;
+    }
 
-            if let Some(name) = name.strip_prefix("%") {
-                modules.insert(name.to_string(), ModuleType::FlipFlop(FlipFlop::new(name)));
-            } else if let Some(name) = name.strip_prefix("&") {
-                modules.insert(
-                    name.to_string(),
-                    ModuleType::Conjunction(Conjunction::new(name)),
-                );
-            }
+    fn kind(&self) -> &'static str {
+        return "inverter";
+    }
+}
+
+// Ignores high pulses and sends a low pulse on every `modulus`-th low pulse.
+struct Counter {
+    count: u64,
+    modulus: u64,
+    sender: Sender,
+}
+
+impl Counter {
+    // None for a modulus of zero, which could never be reached.
+    fn new(name: &str, modulus: u64) -> Option<Self> {
+        if modulus == 0 {
+            return None;
         }
 
-        for line in s.lines() {
-            let mut split = line.split(" -> ");
-            let mut name = split.next().unwrap();
-            if name != "broadcaster" {
-                name = &name[1..];
-            }
+        return Some(Self {
+            count: 0,
+            modulus,
+            sender: Sender::new(name),
+        });
+    }
 
-            for receiver_name in split.next().unwrap().split(", ") {
-                let receiver = modules.get_mut(receiver_name);
-                if receiver.is_some() {
-                    if let ModuleType::Conjunction(conjunction) = receiver.unwrap() {
-                        conjunction
-                            .last_received
-                            .insert(name.to_string(), Pulse::Low);
-                    }
-                }
+    fn parse_state(&self, state: &str) -> Result<u64, InvalidState> {
+        let count: u64 = state.parse().map_err(|_| InvalidState)?;
+        if count >= self.modulus {
+            return Err(InvalidState);
+        }
 
-                let module = modules.get_mut(name);
+        return Ok(count);
+    }
+}
 
-                if module.is_none() {
-                    continue;
-                }
+impl Module for Counter {
+    fn process(&mut self, signal: Signal) -> Vec<Signal> {
+        if signal.pulse == Pulse::High {
+            return Vec::new();
+        }
 
-                match module.unwrap() {
-                    ModuleType::Broadcaster(b) => b.add_receiver(receiver_name),
-                    ModuleType::FlipFlop(f) => f.add_receiver(receiver_name),
-                    ModuleType::Conjunction(c) => c.add_receiver(receiver_name),
-                }
-            }
+        self.count = (self.count + 1) % self.modulus;
+        if self.count == 0 {
+            return self.sender.send(Pulse::Low);
         }
 
-        return Self { map: modules };
+        return Vec::new();
     }
 
-    fn start(&mut self) -> (i64, i64, bool) {
-        let mut q = VecDeque::new();
-        if let ModuleType::Broadcaster(b) = self.map.get_mut("broadcaster").unwrap() {
-            Modules::add_signals(&mut q, b.start());
+    fn add_receiver(&mut self, receiver: &str) {
+        self.sender.add_receiver(receiver);
+    }
+
+    fn receivers(&self) -> &[String] {
+        return &self.sender.receivers;
+    }
+
+    fn kind(&self) -> &'static str {
+        return "counter";
+    }
+
+    fn save(&self) -> Option<String> {
+        return Some(self.count.to_string());
+    }
+
+    fn validate(&self, state: &str) -> Result<(), InvalidState> {
+        return self.parse_state(state).map(|_| ());
+    }
+
+    fn restore(&mut self, state: &str) -> Result<(), InvalidState> {
+        self.count = self.parse_state(state)?;
+        return Ok(());
+    }
+}
+
+// A shift register that starts out full of low pulses. Every received pulse
+// is pushed in and the one falling out the other end is sent.
+struct Delay {
+    line: VecDeque<Pulse>,
+    sender: Sender,
+}
+
+impl Delay {
+    fn new(name: &str, length: usize) -> Self {
+        Self {
+            line: VecDeque::from(vec![Pulse::Low; length]),
+            sender: Sender::new(name),
         }
+    }
 
-        let mut high_count = 0;
-        //we count the first signal from button to broadcaster
-        let mut low_count = 1;
-        let mut sent_to_rx = false;
-        while let Some(signal) = q.pop_front() {
-            match signal.pulse {
-                Pulse::Low => low_count += 1,
-                Pulse::High => high_count += 1,
-            }
+    fn parse_state(&self, state: &str) -> Result<VecDeque<Pulse>, InvalidState> {
+        let line = state
+            .chars()
+            .map(restore_pulse)
+            .collect::<Result<VecDeque<_>, _>>()?;
+        if line.len() != self.line.len() {
+            return Err(InvalidState);
+        }
 
-            if signal.to == "rx" && signal.pulse == Pulse::Low {
-                sent_to_rx = true;
-            }
+        return Ok(line);
+    }
+}
 
-            let to = self.map.get_mut(&signal.to);
-            if to.is_none() {
-                continue;
-            }
+impl Module for Delay {
+    fn process(&mut self, signal: Signal) -> Vec<Signal> {
+        self.line.push_back(signal.pulse);
+        let pulse = self.line.pop_front().unwrap();
+
+        self.sender.send(pulse)
+    }
+
+    fn add_receiver(&mut self, receiver: &str) {
+        self.sender.add_receiver(receiver);
+    }
+
+    fn receivers(&self) -> &[String] {
+        return &self.sender.receivers;
+    }
+
+    fn kind(&self) -> &'static str {
+        return "delay";
+    }
+
+    fn save(&self) -> Option<String> {
+        return Some(self.line.iter().map(|pulse| save_pulse(*pulse)).collect());
+    }
+
+    fn validate(&self, state: &str) -> Result<(), InvalidState> {
+        return self.parse_state(state).map(|_| ());
+    }
+
+    fn restore(&mut self, state: &str) -> Result<(), InvalidState> {
+        self.line = self.parse_state(state)?;
+        return Ok(());
+    }
+}
+
+// How many of the most recent signals an output keeps.
+const OUTPUT_HISTORY: usize = 1024;
+
+// Created for every receiver that is never declared, like `rx` or `output`.
+struct Output {
+    received: Vec<Signal>,
+}
+
+impl Output {
+    fn new() -> Self {
+        Self {
+            received: Vec::new(),
+        }
+    }
+}
+
+impl Module for Output {
+    fn process(&mut self, signal: Signal) -> Vec<Signal> {
+        // Old signals are dropped in batches, so recording stays amortised
+        // O(1) while at most twice the history is held.
+        if self.received.len() == 2 * OUTPUT_HISTORY {
+            self.received.drain(..OUTPUT_HISTORY);
+        }
+        self.received.push(signal);
+
+        Vec::new()
+    }
+
+    fn add_receiver(&mut self, _receiver: &str) {}
+
+    fn receivers(&self) -> &[String] {
+        return &[];
+    }
+
+    fn kind(&self) -> &'static str {
+        return "output";
+    }
+
+    fn received(&self) -> Option<&[Signal]> {
+        let start = self.received.len().saturating_sub(OUTPUT_HISTORY);
+
+        return Some(&self.received[start..]);
+    }
+}
+
+// Takes the module name and the parameter after a `/` in the declaration,
+// and returns None when the parameter does not fit the kind.
+type Factory = Box<dyn Fn(&str, Option<&str>) -> Option<Box<dyn Module>>>;
+
+// Maps the prefix in front of a module name to the kind of module it
+// declares. Prefixes are tried in the order they were registered.
+struct Registry {
+    kinds: Vec<(String, Factory)>,
+}
+
+impl Registry {
+    fn new() -> Self {
+        return Registry { kinds: Vec::new() };
+    }
+
+    // Counters and delay lines take their modulus and length as a
+    // parameter, as in `#count/3` and `~delay/2`.
+    fn standard() -> Self {
+        let mut registry = Registry::new();
+        registry.register("%", |name| Box::new(FlipFlop::new(name)));
+        registry.register("&", |name| Box::new(Conjunction::new(name)));
+        registry.register("!", |name| Box::new(Inverter::new(name)));
+        registry.register_with_parameter("#", |name, modulus| {
+            let counter = Counter::new(name, modulus?.parse().ok()?)?;
+            return Some(Box::new(counter));
+        });
+        registry.register_with_parameter("~", |name, length| {
+            let delay = Delay::new(name, length?.parse().ok()?);
+            return Some(Box::new(delay));
+        });
+
+        return registry;
+    }
 
-            let signals = match to.unwrap() {
-                ModuleType::Broadcaster(bc) => bc.process(signal),
-                ModuleType::FlipFlop(ff) => ff.process(signal),
-                ModuleType::Conjunction(cj) => cj.process(signal),
+    // For kinds that take no parameter.
+    fn register(&mut self, prefix: &str, factory: impl Fn(&str) -> Box<dyn Module> + 'static) {
+        self.register_with_parameter(prefix, move |name, parameter| match parameter {
+            Some(_) => None,
+            None => Some(factory(name)),
+        });
+    }
+
+    fn register_with_parameter(
+        &mut self,
+        prefix: &str,
+        factory: impl Fn(&str, Option<&str>) -> Option<Box<dyn Module>> + 'static,
+    ) {
+        self.kinds.push((prefix.to_string(), Box::new(factory)));
+    }
+
+    fn create<'a>(&self, declaration: &'a str) -> Option<(&'a str, Box<dyn Module>)> {
+        if declaration == "broadcaster" {
+            let broadcaster = Broadcaster {
+                sender: Sender::new(declaration),
             };
+            return Some((declaration, Box::new(broadcaster)));
+        }
 
-            Modules::add_signals(&mut q, signals);
+        for (prefix, factory) in &self.kinds {
+            if let Some(rest) = declaration.strip_prefix(prefix.as_str()) {
+                let (name, parameter) = match rest.split_once("/") {
+                    Some((name, parameter)) => (name, Some(parameter)),
+                    None => (rest, None),
+                };
+
+                return Some((name, factory(name, parameter)?));
+            }
         }
 
-        (low_count, high_count, sent_to_rx)
+        return None;
     }
+}
 
-    fn add_signals(q: &mut VecDeque<Signal>, signals: Vec<Signal>) {
-        for signal in signals {
-            q.push_back(signal);
+#[derive(Debug, PartialEq, Eq)]
+enum ParseError {
+    InvalidDeclaration(String),
+    // Every press starts at the broadcaster, so a circuit without one can
+    // not be simulated.
+    MissingBroadcaster,
+}
+
+// The state of every stateful module, keyed by module name. Output sinks
+// only record history, so they are not part of the circuit state.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Snapshot {
+    states: BTreeMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum RestoreError {
+    Syntax(String),
+    UnknownModule(String),
+    InvalidState(String),
+}
+
+impl Snapshot {
+    fn parse(s: &str) -> Result<Self, RestoreError> {
+        let mut states = BTreeMap::new();
+
+        for line in s.lines() {
+            let (name, state) = line
+                .split_once(" ")
+                .ok_or(RestoreError::Syntax(line.to_string()))?;
+            states.insert(name.to_string(), state.to_string());
         }
+
+        return Ok(Snapshot { states });
     }
 }
 
-fn main() {
-    let input = fs::read_to_string("test1.txt").unwrap();
-    let mut modules = Modules::parse(&input);
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, state) in &self.states {
+            writeln!(f, "{} {}", name, state)?;
+        }
 
-    let mut low = 0;
-    let mut high = 0;
-    let mut button_presses = 0;
-    loop {
-        button_presses += 1;
-        let (low_count, high_count, sent_to_rx) = modules.start();
+        return Ok(());
+    }
+}
 
-        if sent_to_rx {
-            break;
+struct Modules {
+    map: HashMap<String, Box<dyn Module>>,
+}
+
+impl Modules {
+    fn parse(s: &str) -> Self {
+        return Modules::parse_with(s, &Registry::standard())
+            .unwrap_or_else(|error| panic!("Invalid modules: {:?}", error));
+    }
+
+    fn parse_with(s: &str, registry: &Registry) -> Result<Self, ParseError> {
+        let mut modules: HashMap<String, Box<dyn Module>> = HashMap::new();
+        let mut wiring = Vec::new();
+
+        for line in s.lines() {
+            let (declaration, receivers) = line
+                .split_once(" -> ")
+                .ok_or(ParseError::InvalidDeclaration(line.to_string()))?;
+            let (name, module) = registry
+                .create(declaration)
+                .ok_or(ParseError::InvalidDeclaration(declaration.to_string()))?;
+
+            modules.insert(name.to_string(), module);
+            wiring.push((name, receivers));
         }
 
-        low += low_count;
-        high += high_count;
+        if !modules.contains_key("broadcaster") {
+            return Err(ParseError::MissingBroadcaster);
+        }
+
+        for (name, receivers) in wiring {
+            for receiver_name in receivers.split(", ") {
+                modules
+                    .entry(receiver_name.to_string())
+                    .or_insert_with(|| Box::new(Output::new()))
+                    .add_input(name);
+
+                modules.get_mut(name).unwrap().add_receiver(receiver_name);
+            }
+        }
+
+        return Ok(Self { map: modules });
     }
 
-    //Solved part 2 by hand in the end, because it was a pain
-    //to make it generic :(
-    println!("{} {} {}", low, high, low * high);
-    println!("{}", button_presses);
+    fn snapshot(&self) -> Snapshot {
+        let states = self
+            .map
+            .iter()
+            .filter_map(|(name, module)| Some((name.clone(), module.save()?)))
+            .collect();
+
+        return Snapshot { states };
+    }
+
+    // Modules missing from the snapshot keep their current state. Every
+    // entry is checked before any is applied, so a bad snapshot leaves the
+    // circuit untouched.
+    fn restore(&mut self, snapshot: &Snapshot) -> Result<(), RestoreError> {
+        for (name, state) in &snapshot.states {
+            self.map
+                .get(name)
+                .ok_or(RestoreError::UnknownModule(name.clone()))?
+                .validate(state)
+                .map_err(|InvalidState| RestoreError::InvalidState(name.clone()))?;
+        }
+
+        for (name, state) in &snapshot.states {
+            self.map.get_mut(name).unwrap().restore(state).unwrap();
+        }
+
+        return Ok(());
+    }
+
+    // FNV-1a over the saved snapshot, which unlike the standard library
+    // hasher gives the same value on every run and Rust release.
+    fn state_hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in self.snapshot().to_string().bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+
+        return hash;
+    }
+
+    // Presses the button until the circuit returns to a state it was in
+    // before. Returns the press count at which that state was first seen
+    // and the length of the cycle.
+    fn find_cycle(&mut self, max_presses: usize) -> Option<(usize, usize)> {
+        let mut seen = HashMap::new();
+        seen.insert(self.snapshot(), 0);
+
+        for presses in 1..=max_presses {
+            self.start();
+
+            let snapshot = self.snapshot();
+            if let Some(first) = seen.get(&snapshot) {
+                return Some((*first, presses - first));
+            }
+            seen.insert(snapshot, presses);
+        }
+
+        return None;
+    }
+
+    fn received(&self, output: &str) -> Option<&[Signal]> {
+        return self.map.get(output)?.received();
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        let mut outputs: Vec<&str> = self
+            .map
+            .iter()
+            .filter(|(_, module)| module.received().is_some())
+            .map(|(name, _)| name.as_str())
+            .collect();
+        outputs.sort();
+
+        return outputs;
+    }
+
+    fn start(&mut self) -> (i64, i64, bool) {
+        let mut high_count = 0;
+        let mut low_count = 0;
+        let mut sent_to_rx = false;
+        self.press(|signal| {
+            match signal.pulse {
+                Pulse::Low => low_count += 1,
+                Pulse::High => high_count += 1,
+            }
+
+            if signal.to == "rx" && signal.pulse == Pulse::Low {
+                sent_to_rx = true;
+            }
+        });
+
+        (low_count, high_count, sent_to_rx)
+    }
+
+    // Pushes the button once, handing every signal to `observe` in the order
+    // it is delivered, starting with the button's low pulse to the broadcaster.
+    fn press(&mut self, mut observe: impl FnMut(&Signal)) {
+        let mut q = VecDeque::new();
+        q.push_back(Signal {
+            from: String::from("button"),
+            to: String::from("broadcaster"),
+            pulse: Pulse::Low,
+        });
+
+        while let Some(signal) = q.pop_front() {
+            observe(&signal);
+
+            let signals = self.map.get_mut(&signal.to).unwrap().process(signal);
+
+            Modules::add_signals(&mut q, signals);
+        }
+    }
+
+    fn add_signals(q: &mut VecDeque<Signal>, signals: Vec<Signal>) {
+        for signal in signals {
+            q.push_back(signal);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Event {
+    press: usize,
+    seq: usize,
+    signal: Signal,
+}
+
+// Tarjan's algorithm over the module graph, with names visited in sorted
+// order so the components come out in a stable order.
+struct Tarjan<'a> {
+    graph: &'a BTreeMap<&'a str, Vec<&'a str>>,
+    index: HashMap<&'a str, usize>,
+    low: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    on_stack: HashSet<&'a str>,
+    components: Vec<Vec<String>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, node: &'a str) {
+        let index = self.index.len();
+        self.index.insert(node, index);
+        self.low.insert(node, index);
+        self.stack.push(node);
+        self.on_stack.insert(node);
+
+        for &next in &self.graph[node] {
+            if !self.index.contains_key(next) {
+                self.visit(next);
+                let low = self.low[node].min(self.low[next]);
+                self.low.insert(node, low);
+            } else if self.on_stack.contains(next) {
+                let low = self.low[node].min(self.index[next]);
+                self.low.insert(node, low);
+            }
+        }
+
+        if self.low[node] == index {
+            let mut component = Vec::new();
+            loop {
+                let member = self.stack.pop().unwrap();
+                self.on_stack.remove(member);
+                component.push(member.to_string());
+                if member == node {
+                    break;
+                }
+            }
+
+            component.sort();
+            self.components.push(component);
+        }
+    }
+}
+
+// A chain of flip-flops counting button presses in binary, least significant
+// bit first. The flip-flops whose bit is set in `period` feed the hub
+// conjunction, which resets the chain to zero once they are all on.
+#[derive(Debug, PartialEq, Eq)]
+struct CounterCircuit {
+    hub: String,
+    bits: Vec<String>,
+    period: u64,
+}
+
+impl CounterCircuit {
+    // One digit per flip-flop in `bits`, so the lowest bit comes first.
+    fn pattern(&self) -> String {
+        return (0..self.bits.len())
+            .map(|i| if self.period >> i & 1 == 1 { '1' } else { '0' })
+            .collect();
+    }
+}
+
+struct CircuitAnalysis {
+    components: Vec<Vec<String>>,
+    counters: Vec<CounterCircuit>,
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        return a;
+    }
+
+    return gcd(b, a % b);
+}
+
+impl CircuitAnalysis {
+    // The press on which every counter resets at the same time.
+    fn common_period(&self) -> Option<u64> {
+        if self.counters.is_empty() {
+            return None;
+        }
+
+        return Some(self.counters.iter().fold(1, |acc, counter| {
+            acc / gcd(acc, counter.period) * counter.period
+        }));
+    }
+
+    fn summary(&self) -> String {
+        let mut summary = format!("{} strongly connected components\n", self.components.len());
+
+        for component in &self.components {
+            if component.len() > 1
+                && !self
+                    .counters
+                    .iter()
+                    .any(|counter| component.contains(&counter.hub))
+            {
+                summary.push_str(&format!("sub-circuit: {}\n", component.join(", ")));
+            }
+        }
+
+        for counter in &self.counters {
+            summary.push_str(&format!(
+                "counter {}: {} bits {}, pattern {}, period {}\n",
+                counter.hub,
+                counter.bits.len(),
+                counter.bits.join(" "),
+                counter.pattern(),
+                counter.period
+            ));
+        }
+
+        if let Some(period) = self.common_period() {
+            summary.push_str(&format!(
+                "all counters reset together every {} presses\n",
+                period
+            ));
+        }
+
+        return summary;
+    }
+}
+
+impl Modules {
+    fn graph(&self) -> BTreeMap<&str, Vec<&str>> {
+        return self
+            .map
+            .iter()
+            .map(|(name, module)| {
+                let receivers = module.receivers().iter().map(|r| r.as_str()).collect();
+                (name.as_str(), receivers)
+            })
+            .collect();
+    }
+
+    fn components(&self) -> Vec<Vec<String>> {
+        let graph = self.graph();
+        let mut tarjan = Tarjan {
+            graph: &graph,
+            index: HashMap::new(),
+            low: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            components: Vec::new(),
+        };
+
+        for &node in graph.keys() {
+            if !tarjan.index.contains_key(node) {
+                tarjan.visit(node);
+            }
+        }
+
+        return tarjan.components;
+    }
+
+    // Recognises a counter purely from the wiring of a component: a single
+    // conjunction hub and a chain of flip-flops entered from outside at its
+    // lowest bit, where the hub feeds the lowest bit and every clear bit.
+    fn counter(&self, component: &[String]) -> Option<CounterCircuit> {
+        let kind = |name: &str| self.map[name].kind();
+        let sends_to = |from: &str, to: &str| self.map[from].receivers().iter().any(|r| r == to);
+
+        let hubs: Vec<&String> = component
+            .iter()
+            .filter(|name| kind(name) == "conjunction")
+            .collect();
+        if hubs.len() != 1 || component.len() < 2 {
+            return None;
+        }
+        let hub = hubs[0];
+        if component
+            .iter()
+            .any(|name| name != hub && kind(name) != "flip-flop")
+        {
+            return None;
+        }
+
+        let entered_from_outside = |name: &&String| {
+            self.map.iter().any(|(from, module)| {
+                !component.contains(from) && module.receivers().contains(name)
+            })
+        };
+        let heads: Vec<&String> = component
+            .iter()
+            .filter(|name| *name != hub)
+            .filter(entered_from_outside)
+            .collect();
+        if heads.len() != 1 {
+            return None;
+        }
+
+        let mut bits = vec![heads[0].clone()];
+        loop {
+            let next: Vec<&String> = self.map[bits.last().unwrap()]
+                .receivers()
+                .iter()
+                .filter(|name| *name != hub && component.contains(name))
+                .collect();
+
+            match next.len() {
+                0 => break,
+                1 if !bits.contains(next[0]) => bits.push(next[0].clone()),
+                _ => return None,
+            }
+        }
+        // The period has one bit per flip-flop and must fit in a u64.
+        if bits.len() != component.len() - 1 || bits.len() >= 64 {
+            return None;
+        }
+
+        let mut period = 0;
+        for (i, bit) in bits.iter().enumerate() {
+            let set = sends_to(bit, hub);
+            if set {
+                period |= 1 << i;
+            }
+
+            if sends_to(hub, bit) != (i == 0 || !set) {
+                return None;
+            }
+        }
+        if period & 1 == 0 {
+            return None;
+        }
+
+        return Some(CounterCircuit {
+            hub: hub.clone(),
+            bits,
+            period,
+        });
+    }
+
+    fn analyze(&self) -> CircuitAnalysis {
+        let components = self.components();
+        let counters = components
+            .iter()
+            .filter_map(|component| self.counter(component))
+            .collect();
+
+        return CircuitAnalysis {
+            components,
+            counters,
+        };
+    }
+}
+
+struct Checkpoint {
+    presses: usize,
+    events: usize,
+    snapshot: Snapshot,
+    // Output history is not circuit state, so it is kept apart from the
+    // snapshot.
+    outputs: BTreeMap<String, Vec<Signal>>,
+}
+
+impl Checkpoint {
+    // Events are not saved, so a checkpoint read back from a file clears
+    // the simulator's events when rewinding to it.
+    fn parse(s: &str) -> Result<Self, RestoreError> {
+        let (header, states) = s
+            .split_once("\n\n")
+            .ok_or(RestoreError::Syntax(s.to_string()))?;
+        let mut lines = header.lines();
+
+        let first = lines.next().unwrap_or_default();
+        let presses = first
+            .strip_prefix("presses ")
+            .and_then(|presses| presses.parse().ok())
+            .ok_or(RestoreError::Syntax(first.to_string()))?;
+
+        let mut outputs = BTreeMap::new();
+        for line in lines {
+            let syntax = || RestoreError::Syntax(line.to_string());
+            let mut fields = line.split(' ');
+            if fields.next() != Some("output") {
+                return Err(syntax());
+            }
+            let name = fields.next().ok_or_else(syntax)?;
+
+            // Every received signal is its sender followed by the pulse.
+            let mut received = Vec::new();
+            for field in fields {
+                let pulse = field.chars().last().ok_or_else(syntax)?;
+                let from = &field[..field.len() - pulse.len_utf8()];
+                if from.is_empty() {
+                    return Err(syntax());
+                }
+
+                received.push(Signal {
+                    from: from.to_string(),
+                    to: name.to_string(),
+                    pulse: restore_pulse(pulse).map_err(|InvalidState| syntax())?,
+                });
+            }
+            outputs.insert(name.to_string(), received);
+        }
+
+        return Ok(Checkpoint {
+            presses,
+            events: 0,
+            snapshot: Snapshot::parse(states)?,
+            outputs,
+        });
+    }
+}
+
+// The press count and output history come first, then a blank line and the
+// snapshot.
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "presses {}", self.presses)?;
+        for (name, received) in &self.outputs {
+            write!(f, "output {}", name)?;
+            for signal in received {
+                write!(f, " {}{}", signal.from, save_pulse(signal.pulse))?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f)?;
+
+        return write!(f, "{}", self.snapshot);
+    }
+}
+
+struct Simulator {
+    modules: Modules,
+    presses: usize,
+    events: Vec<Event>,
+}
+
+impl Simulator {
+    fn new(modules: Modules) -> Self {
+        return Simulator {
+            modules,
+            presses: 0,
+            events: Vec::new(),
+        };
+    }
+
+    fn press(&mut self) -> &[Event] {
+        let press = self.presses;
+        let first = self.events.len();
+        let events = &mut self.events;
+
+        self.modules.press(|signal| {
+            events.push(Event {
+                press,
+                seq: events.len() - first,
+                signal: signal.clone(),
+            })
+        });
+        self.presses += 1;
+
+        return &self.events[first..];
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        let outputs = self
+            .modules
+            .outputs()
+            .into_iter()
+            .map(|name| {
+                (
+                    name.to_string(),
+                    self.modules.received(name).unwrap().to_vec(),
+                )
+            })
+            .collect();
+
+        return Checkpoint {
+            presses: self.presses,
+            events: self.events.len(),
+            snapshot: self.modules.snapshot(),
+            outputs,
+        };
+    }
+
+    // Drops everything recorded after the checkpoint, so the simulation can
+    // branch off from it again.
+    fn rewind(&mut self, checkpoint: &Checkpoint) -> Result<(), RestoreError> {
+        // Only output sinks may be replaced by the saved history.
+        for name in checkpoint.outputs.keys() {
+            if self.modules.received(name).is_none() {
+                return Err(RestoreError::UnknownModule(name.clone()));
+            }
+        }
+
+        self.modules.restore(&checkpoint.snapshot)?;
+        for (name, received) in &checkpoint.outputs {
+            let output = Output {
+                received: received.clone(),
+            };
+            self.modules.map.insert(name.clone(), Box::new(output));
+        }
+        self.presses = checkpoint.presses;
+        self.events.truncate(checkpoint.events);
+
+        return Ok(());
+    }
+
+    fn run(&mut self, presses: usize) {
+        for _ in 0..presses {
+            self.press();
+        }
+    }
+
+    // Every signal takes one time step. Each sender gets a wire holding the
+    // last pulse it sent, which for a flip-flop is its on/off state.
+    fn to_vcd(&self) -> String {
+        let senders: BTreeSet<&str> = self
+            .events
+            .iter()
+            .map(|event| event.signal.from.as_str())
+            .collect();
+        let ids: HashMap<&str, String> = senders
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (*name, vcd_identifier(i + 1)))
+            .collect();
+        let press_id = vcd_identifier(0);
+
+        let mut vcd = String::new();
+        vcd.push_str("$timescale 1ns $end\n");
+        vcd.push_str("$scope module circuit $end\n");
+        vcd.push_str(&format!("$var integer 32 {} press $end\n", press_id));
+        for name in &senders {
+            vcd.push_str(&format!("$var wire 1 {} {} $end\n", ids[name], name));
+        }
+        vcd.push_str("$upscope $end\n");
+        vcd.push_str("$enddefinitions $end\n");
+
+        vcd.push_str("$dumpvars\n");
+        vcd.push_str(&format!("b0 {}\n", press_id));
+        for name in &senders {
+            vcd.push_str(&format!("0{}\n", ids[name]));
+        }
+        vcd.push_str("$end\n");
+
+        for (time, event) in self.events.iter().enumerate() {
+            vcd.push_str(&format!("#{}\n", time));
+            if event.seq == 0 {
+                vcd.push_str(&format!("b{:b} {}\n", event.press, press_id));
+            }
+
+            let value = match event.signal.pulse {
+                Pulse::Low => 0,
+                Pulse::High => 1,
+            };
+            vcd.push_str(&format!("{}{}\n", value, ids[event.signal.from.as_str()]));
+        }
+        vcd.push_str(&format!("#{}\n", self.events.len()));
+
+        return vcd;
+    }
+}
+
+// VCD identifiers are short strings of printable ASCII characters.
+fn vcd_identifier(mut index: usize) -> String {
+    let mut id = String::new();
+
+    loop {
+        id.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            return id;
+        }
+        index -= 1;
+    }
+}
+
+// Continues the simulation from a saved checkpoint, or from the first press
+// without one, and returns the checkpoint after the extra presses.
+fn branch(
+    simulator: &mut Simulator,
+    saved: Option<&str>,
+    presses: usize,
+) -> Result<Checkpoint, RestoreError> {
+    if let Some(saved) = saved {
+        simulator.rewind(&Checkpoint::parse(saved)?)?;
+    }
+    simulator.run(presses);
+
+    return Ok(simulator.checkpoint());
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let position = args.iter().position(|arg| arg == flag)?;
+
+    return args.get(position + 1).map(|value| value.as_str());
+}
+
+fn main() {
+    let input = fs::read_to_string("test1.txt").unwrap();
+    let mut modules = Modules::parse(&input);
+    let args: Vec<String> = env::args().collect();
+
+    // With --branch, --restore and --save read and write checkpoints of the
+    // simulation instead of snapshots of the circuit.
+    if let Some(presses) = flag_value(&args, "--branch") {
+        let Ok(presses) = presses.parse() else {
+            eprintln!("usage: --branch <presses> [--restore <checkpoint>] [--save <checkpoint>]");
+            process::exit(1);
+        };
+
+        let saved = flag_value(&args, "--restore").map(|file| fs::read_to_string(file).unwrap());
+        let mut simulator = Simulator::new(Modules::parse(&input));
+        let checkpoint = branch(&mut simulator, saved.as_deref(), presses).unwrap();
+        println!(
+            "press {} state {:016x}",
+            checkpoint.presses,
+            simulator.modules.state_hash()
+        );
+
+        if let Some(file) = flag_value(&args, "--save") {
+            fs::write(file, checkpoint.to_string()).unwrap();
+        }
+        return;
+    }
+
+    if let Some(file) = flag_value(&args, "--restore") {
+        let snapshot = Snapshot::parse(&fs::read_to_string(file).unwrap()).unwrap();
+        modules.restore(&snapshot).unwrap();
+        println!("restored state {:016x}", modules.state_hash());
+    }
+
+    if args.iter().any(|arg| arg == "--vcd") {
+        let mut simulator = Simulator::new(Modules::parse(&input));
+        simulator.run(1000);
+        fs::write("pulses.vcd", simulator.to_vcd()).unwrap();
+    }
+
+    if let Some(file) = flag_value(&args, "--analyze") {
+        let modules = Modules::parse(&fs::read_to_string(file).unwrap());
+        print!("{}", modules.analyze().summary());
+    }
+
+    if args.iter().any(|arg| arg == "--cycle") {
+        match Modules::parse(&input).find_cycle(100_000) {
+            Some((first, length)) => {
+                println!("state after {} presses repeats every {}", first, length)
+            }
+            None => println!("no repeated state within 100000 presses"),
+        }
+    }
+
+    let mut low = 0;
+    let mut high = 0;
+    let mut button_presses = 0;
+    loop {
+        button_presses += 1;
+        let (low_count, high_count, sent_to_rx) = modules.start();
+
+        if sent_to_rx {
+            break;
+        }
+
+        low += low_count;
+        high += high_count;
+    }
+
+    //Solved part 2 by hand in the end, because it was a pain
+    //to make it generic :(
+    println!("{} {} {}", low, high, low * high);
+    println!("{}", button_presses);
+
+    if args.iter().any(|arg| arg == "--outputs") {
+        for output in modules.outputs() {
+            let received = modules.received(output).unwrap();
+            let low = received
+                .iter()
+                .filter(|signal| signal.pulse == Pulse::Low)
+                .count();
+            println!(
+                "{}: {} low and {} high of the last {} pulses",
+                output,
+                low,
+                received.len() - low,
+                received.len()
+            );
+        }
+    }
+
+    if let Some(file) = flag_value(&args, "--save") {
+        fs::write(file, modules.snapshot().to_string()).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{
+        branch, vcd_identifier, Checkpoint, Counter, Modules, ParseError, Pulse, Registry,
+        RestoreError, Signal, Simulator, Snapshot, OUTPUT_HISTORY,
+    };
+
+    #[test]
+    fn components() {
+        let input = fs::read_to_string("test2.txt").unwrap();
+        let analysis = Modules::parse(&input).analyze();
+
+        assert!(analysis.components.contains(&vec![
+            String::from("a"),
+            String::from("b"),
+            String::from("c"),
+            String::from("inv")
+        ]));
+        assert_eq!(analysis.components.len(), 2);
+        assert!(analysis.counters.is_empty());
+        assert_eq!(analysis.common_period(), None);
+
+        let input = fs::read_to_string("test1.txt").unwrap();
+        let analysis = Modules::parse(&input).analyze();
+        assert!(analysis
+            .components
+            .iter()
+            .all(|component| component.len() == 1));
+        assert!(analysis.counters.is_empty());
+    }
+
+    #[test]
+    fn counters_match_simulation() {
+        let input = fs::read_to_string("input.txt").unwrap();
+        let mut modules = Modules::parse(&input);
+        let analysis = modules.analyze();
+        assert_eq!(analysis.counters.len(), 4);
+
+        let mut first_reset = std::collections::HashMap::new();
+        for press in 1..=4096 {
+            modules.press(|signal| {
+                if signal.pulse == Pulse::Low {
+                    first_reset.entry(signal.from.clone()).or_insert(press);
+                }
+            });
+        }
+
+        for counter in &analysis.counters {
+            assert_eq!(counter.bits.len(), 12);
+            assert_eq!(first_reset[&counter.hub], counter.period);
+            let binary: String = counter.pattern().chars().rev().collect();
+            assert_eq!(u64::from_str_radix(&binary, 2).unwrap(), counter.period);
+        }
+    }
+
+    #[test]
+    fn counter_from_wiring() {
+        let modules = Modules::parse(
+            "broadcaster -> b0\n%b0 -> hub, b1\n%b1 -> b2\n%b2 -> hub\n&hub -> b0, b1, out",
+        );
+        let analysis = modules.analyze();
+
+        assert_eq!(analysis.counters.len(), 1);
+        let counter = &analysis.counters[0];
+        assert_eq!(counter.hub, "hub");
+        assert_eq!(counter.bits, vec!["b0", "b1", "b2"]);
+        assert_eq!(counter.pattern(), "101");
+        assert_eq!(counter.period, 5);
+        assert_eq!(analysis.common_period(), Some(5));
+        assert!(analysis
+            .summary()
+            .contains("counter hub: 3 bits b0 b1 b2, pattern 101, period 5\n"));
+
+        let modules = Modules::parse(
+            "broadcaster -> b0\n%b0 -> hub, b1\n%b1 -> b2\n%b2 -> hub\n&hub -> b0, out",
+        );
+        assert!(modules.analyze().counters.is_empty());
+    }
+
+    #[test]
+    fn counter_too_long_for_period() {
+        // Every bit feeds the hub, so the period is all ones.
+        let chain = |length: usize| {
+            let mut lines = vec![
+                "broadcaster -> b0".to_string(),
+                "&hub -> b0, out".to_string(),
+            ];
+            for i in 0..length {
+                let next = if i + 1 < length {
+                    format!(", b{}", i + 1)
+                } else {
+                    String::new()
+                };
+                lines.push(format!("%b{} -> hub{}", i, next));
+            }
+
+            return Modules::parse(&lines.join("\n")).analyze();
+        };
+
+        let analysis = chain(63);
+        assert_eq!(analysis.counters.len(), 1);
+        assert_eq!(analysis.counters[0].period, u64::MAX >> 1);
+
+        assert!(chain(64).counters.is_empty());
+        assert!(chain(100).counters.is_empty());
+    }
+
+    #[test]
+    fn counter_pattern_follows_bits() {
+        let mut modules = Modules::parse(
+            "broadcaster -> b0\n%b0 -> hub, b1\n%b1 -> hub, b2\n%b2 -> b3\n%b3 -> hub\n&hub -> b0, b2, out",
+        );
+        let analysis = modules.analyze();
+
+        assert_eq!(analysis.counters.len(), 1);
+        let counter = &analysis.counters[0];
+        assert_eq!(counter.bits, vec!["b0", "b1", "b2", "b3"]);
+        assert_eq!(counter.period, 11);
+        assert_eq!(counter.pattern(), "1101");
+        assert!(analysis
+            .summary()
+            .contains("counter hub: 4 bits b0 b1 b2 b3, pattern 1101, period 11\n"));
+
+        let mut presses = 0;
+        let mut reset = false;
+        while !reset {
+            presses += 1;
+            modules.press(|signal| reset |= signal.from == "hub" && signal.pulse == Pulse::Low);
+        }
+        assert_eq!(presses, 11);
+    }
+
+    fn push_button(file: &str, presses: usize) -> i64 {
+        let input = fs::read_to_string(file).unwrap();
+        let mut modules = Modules::parse(&input);
+
+        let mut low = 0;
+        let mut high = 0;
+        for _ in 0..presses {
+            let (low_count, high_count, _) = modules.start();
+            low += low_count;
+            high += high_count;
+        }
+
+        return low * high;
+    }
+
+    #[test]
+    fn start() {
+        assert_eq!(push_button("test2.txt", 1000), 32000000);
+        assert_eq!(push_button("test1.txt", 1000), 11687500);
+    }
+
+    #[test]
+    fn undeclared_receivers_become_outputs() {
+        let input = fs::read_to_string("test1.txt").unwrap();
+        let mut modules = Modules::parse(&input);
+        modules.start();
+
+        let received = modules.received("rx").unwrap();
+        assert_eq!(received.len(), 2);
+        assert!(received.iter().all(|signal| signal.from == "con"));
+        assert_eq!(received[0].pulse, Pulse::High);
+        assert_eq!(received[1].pulse, Pulse::Low);
+        assert!(modules.received("con").is_none());
+        assert_eq!(modules.outputs(), vec!["rx"]);
+
+        for _ in 0..1000 {
+            modules.start();
+        }
+        let received = modules.received("rx").unwrap();
+        assert_eq!(received.len(), OUTPUT_HISTORY);
+        assert_eq!(received[OUTPUT_HISTORY - 1].pulse, Pulse::Low);
+        assert_eq!(received[OUTPUT_HISTORY - 2].pulse, Pulse::High);
+    }
+
+    #[test]
+    fn registered_kinds() {
+        let mut modules = Modules::parse(
+            "broadcaster -> inv, count, delay\n!inv -> out\n#count/3 -> out\n~delay/2 -> out",
+        );
+        for _ in 0..3 {
+            modules.start();
+        }
+
+        let received: Vec<(&str, Pulse)> = modules
+            .received("out")
+            .unwrap()
+            .iter()
+            .map(|signal| (signal.from.as_str(), signal.pulse))
+            .collect();
+        assert_eq!(
+            received,
+            vec![
+                ("inv", Pulse::High),
+                ("delay", Pulse::Low),
+                ("inv", Pulse::High),
+                ("delay", Pulse::Low),
+                ("inv", Pulse::High),
+                ("count", Pulse::Low),
+                ("delay", Pulse::Low),
+            ]
+        );
+
+        let registry = Registry::standard();
+        for declaration in ["?x", "#count", "#count/0", "#count/x", "~delay/-1", "%ff/2"] {
+            let line = format!("{} -> y", declaration);
+            assert_eq!(
+                Modules::parse_with(&line, &registry).err(),
+                Some(ParseError::InvalidDeclaration(declaration.to_string()))
+            );
+        }
+        assert_eq!(
+            Modules::parse_with("broadcaster -> a\n%a", &registry).err(),
+            Some(ParseError::InvalidDeclaration("%a".to_string()))
+        );
+        assert_eq!(
+            Modules::parse_with("%a -> b\n&b -> a", &registry).err(),
+            Some(ParseError::MissingBroadcaster)
+        );
+        assert!(Counter::new("count", 0).is_none());
+
+        let mut registry = Registry::new();
+        registry.register("@", |name| Box::new(Counter::new(name, 2).unwrap()));
+        let mut modules =
+            Modules::parse_with("broadcaster -> half\n@half -> out", &registry).unwrap();
+        modules.start();
+        modules.start();
+        assert_eq!(modules.received("out").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let input = fs::read_to_string("test1.txt").unwrap();
+        let mut modules = Modules::parse(&input);
+        let initial = modules.snapshot();
+        assert_eq!(initial.to_string(), "a off\nb off\ncon a=0,b=0\ninv a=0\n");
+        // The hash only depends on the snapshot text, so it is fixed.
+        assert_eq!(modules.state_hash(), 0x228df13366bd373a);
+
+        modules.start();
+        let snapshot = modules.snapshot();
+        assert_eq!(snapshot.to_string(), "a on\nb on\ncon a=1,b=1\ninv a=1\n");
+        assert_eq!(Snapshot::parse(&snapshot.to_string()).unwrap(), snapshot);
+        assert_ne!(modules.state_hash(), Modules::parse(&input).state_hash());
+
+        modules.restore(&initial).unwrap();
+        assert_eq!(modules.snapshot(), initial);
+        assert_eq!(modules.state_hash(), Modules::parse(&input).state_hash());
+    }
+
+    #[test]
+    fn restore_errors() {
+        let input = fs::read_to_string("test1.txt").unwrap();
+        let mut modules = Modules::parse(&input);
+
+        let snapshot = Snapshot::parse("zz on").unwrap();
+        assert_eq!(
+            modules.restore(&snapshot),
+            Err(RestoreError::UnknownModule(String::from("zz")))
+        );
+
+        let snapshot = Snapshot::parse("con a=1").unwrap();
+        assert_eq!(
+            modules.restore(&snapshot),
+            Err(RestoreError::InvalidState(String::from("con")))
+        );
+
+        let snapshot = Snapshot::parse("a maybe").unwrap();
+        assert_eq!(
+            modules.restore(&snapshot),
+            Err(RestoreError::InvalidState(String::from("a")))
+        );
+
+        assert_eq!(
+            Snapshot::parse("a"),
+            Err(RestoreError::Syntax(String::from("a")))
+        );
+    }
+
+    #[test]
+    fn failed_restore_changes_nothing() {
+        let input = fs::read_to_string("test1.txt").unwrap();
+        let mut modules = Modules::parse(&input);
+        let initial = modules.snapshot();
+
+        let snapshot = Snapshot::parse("a on\nb on\ncon a=1,b=2").unwrap();
+        assert_eq!(
+            modules.restore(&snapshot),
+            Err(RestoreError::InvalidState(String::from("con")))
+        );
+        assert_eq!(modules.snapshot(), initial);
+
+        let snapshot = Snapshot::parse("a on\nzz on").unwrap();
+        assert_eq!(
+            modules.restore(&snapshot),
+            Err(RestoreError::UnknownModule(String::from("zz")))
+        );
+        assert_eq!(modules.snapshot(), initial);
+    }
+
+    #[test]
+    fn extra_kinds_snapshot() {
+        let declarations = "broadcaster -> count, delay\n#count/3 -> out\n~delay/2 -> out";
+
+        let mut modules = Modules::parse(declarations);
+        modules.start();
+        modules.start();
+        let snapshot = modules.snapshot();
+        assert_eq!(snapshot.to_string(), "count 2\ndelay 00\n");
+
+        let mut other = Modules::parse(declarations);
+        other.restore(&snapshot).unwrap();
+        assert_eq!(other.state_hash(), modules.state_hash());
+        assert_eq!(
+            other.restore(&Snapshot::parse("count 3").unwrap()),
+            Err(RestoreError::InvalidState(String::from("count")))
+        );
+    }
+
+    #[test]
+    fn find_cycle() {
+        let input = fs::read_to_string("test1.txt").unwrap();
+        assert_eq!(Modules::parse(&input).find_cycle(100), Some((0, 4)));
+
+        let input = fs::read_to_string("test2.txt").unwrap();
+        assert_eq!(Modules::parse(&input).find_cycle(100), Some((0, 1)));
+
+        let mut modules = Modules::parse(&input);
+        assert_eq!(modules.find_cycle(0), None);
+    }
+
+    #[test]
+    fn branch_from_checkpoint() {
+        let input = fs::read_to_string("test1.txt").unwrap();
+        let mut simulator = Simulator::new(Modules::parse(&input));
+        simulator.run(3);
+
+        let checkpoint = simulator.checkpoint();
+        simulator.run(5);
+        let first_branch = simulator.events[checkpoint.events..].to_vec();
+
+        simulator.rewind(&checkpoint).unwrap();
+        assert_eq!(simulator.presses, 3);
+        assert_eq!(simulator.events.len(), checkpoint.events);
+
+        simulator.run(5);
+        assert_eq!(simulator.events[checkpoint.events..], first_branch[..]);
+    }
+
+    #[test]
+    fn branch_through_saved_checkpoint() {
+        let input = fs::read_to_string("test1.txt").unwrap();
+        let mut straight = Simulator::new(Modules::parse(&input));
+        let expected = branch(&mut straight, None, 8).unwrap();
+
+        let mut first = Simulator::new(Modules::parse(&input));
+        let saved = branch(&mut first, None, 3).unwrap().to_string();
+        assert!(saved.starts_with("presses 3\noutput rx "));
+        assert_eq!(Checkpoint::parse(&saved).unwrap().to_string(), saved);
+
+        let mut resumed = Simulator::new(Modules::parse(&input));
+        let checkpoint = branch(&mut resumed, Some(&saved), 5).unwrap();
+        assert_eq!(checkpoint.presses, 8);
+        assert_eq!(checkpoint.snapshot, expected.snapshot);
+        assert_eq!(checkpoint.outputs, expected.outputs);
+        assert_eq!(resumed.modules.state_hash(), straight.modules.state_hash());
+        assert_eq!(
+            resumed.events[..],
+            straight.events[straight.events.len() - resumed.events.len()..]
+        );
+
+        let error =
+            |saved: &str| branch(&mut Simulator::new(Modules::parse(&input)), Some(saved), 1).err();
+        assert_eq!(
+            error("presses x\n\n"),
+            Some(RestoreError::Syntax("presses x".to_string()))
+        );
+        assert_eq!(
+            error("presses 1\noutput rx a2\n\n"),
+            Some(RestoreError::Syntax("output rx a2".to_string()))
+        );
+        assert_eq!(
+            error("presses 1\noutput broadcaster\n\n"),
+            Some(RestoreError::UnknownModule("broadcaster".to_string()))
+        );
+    }
+
+    #[test]
+    fn rewind_restores_output_history() {
+        let input = fs::read_to_string("test1.txt").unwrap();
+        let mut simulator = Simulator::new(Modules::parse(&input));
+        simulator.run(3);
+
+        let checkpoint = simulator.checkpoint();
+        let received = simulator.modules.received("rx").unwrap().to_vec();
+        assert!(!received.is_empty());
+
+        simulator.run(5);
+        let first_branch = simulator.modules.received("rx").unwrap().to_vec();
+        assert!(first_branch.len() > received.len());
+
+        simulator.rewind(&checkpoint).unwrap();
+        assert_eq!(simulator.modules.received("rx").unwrap(), &received[..]);
+
+        simulator.run(5);
+        assert_eq!(simulator.modules.received("rx").unwrap(), &first_branch[..]);
+    }
+
+    #[test]
+    fn simulator_records_every_signal() {
+        let input = fs::read_to_string("test2.txt").unwrap();
+        let mut simulator = Simulator::new(Modules::parse(&input));
+
+        let events = simulator.press();
+        assert_eq!(events.len(), 12);
+        assert_eq!(
+            events[0].signal,
+            Signal {
+                from: String::from("button"),
+                to: String::from("broadcaster"),
+                pulse: Pulse::Low,
+            }
+        );
+        assert_eq!(events[11].signal.from, "inv");
+        assert_eq!(events[11].signal.to, "a");
+        assert_eq!(events[11].signal.pulse, Pulse::High);
+        assert!(events
+            .iter()
+            .enumerate()
+            .all(|(i, event)| event.press == 0 && event.seq == i));
+
+        simulator.run(999);
+        assert_eq!(simulator.presses, 1000);
+        assert_eq!(simulator.events.len(), 12000);
+        assert_eq!(simulator.events.last().unwrap().press, 999);
+    }
+
+    #[test]
+    fn to_vcd() {
+        let input = fs::read_to_string("test1.txt").unwrap();
+        let mut simulator = Simulator::new(Modules::parse(&input));
+        simulator.run(2);
+
+        let vcd = simulator.to_vcd();
+        assert!(vcd.starts_with("$timescale 1ns $end\n$scope module circuit $end\n"));
+        assert!(vcd.contains("$var integer 32 ! press $end\n"));
+        assert!(vcd.contains("$var wire 1 \" a $end\n"));
+        assert!(vcd.contains("$var wire 1 # b $end\n"));
+        assert!(vcd.contains("#8\nb1 !\n"));
+        assert!(vcd.ends_with(&format!("#{}\n", simulator.events.len())));
+    }
+
+    #[test]
+    fn vcd_identifiers_are_unique() {
+        let ids: std::collections::HashSet<String> = (0..20000).map(vcd_identifier).collect();
+        assert_eq!(ids.len(), 20000);
+        assert!(ids
+            .iter()
+            .all(|id| id.chars().all(|c| ('!'..='~').contains(&c))));
+    }
 }
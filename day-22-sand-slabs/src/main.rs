@@ -1,14 +1,32 @@
+#![allow(clippy::needless_return)]
+
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
-    fs,
+    collections::{HashMap, HashSet},
+    env, fs,
 };
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Point {
     x: u64,
     y: u64,
     z: u64,
 }
 
+impl Point {
+    fn parse(s: &str) -> Option<Self> {
+        let coords = s
+            .split(",")
+            .map(|coord| coord.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        let [x, y, z] = coords[..] else {
+            return None;
+        };
+
+        return Some(Point { x, y, z });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Brick {
     start: Point,
     end: Point,
@@ -16,71 +34,153 @@ struct Brick {
 }
 
 impl Brick {
-    fn parse(s: &str, id: usize) -> Self {
-        let mut split = s.split("~");
+    fn parse(s: &str, id: usize) -> Result<Self, BrickError> {
+        let malformed = || BrickError::Malformed(id);
+        let (start, end) = s.split_once("~").ok_or_else(malformed)?;
 
-        let start = split
-            .next()
-            .unwrap()
-            .split(",")
-            .map(|coord| coord.parse::<u64>().unwrap())
-            .collect::<Vec<_>>();
+        Ok(Self {
+            start: Point::parse(start).ok_or_else(malformed)?,
+            end: Point::parse(end).ok_or_else(malformed)?,
+            id,
+        })
+    }
 
-        let end = split
-            .next()
-            .unwrap()
-            .split(",")
-            .map(|coord| coord.parse::<u64>().unwrap())
-            .collect::<Vec<_>>();
+    // The (x, y) cells the brick covers when seen from above.
+    fn get_area(&self) -> Vec<(u64, u64)> {
+        let mut area = Vec::new();
+        for x in self.start.x..=self.end.x {
+            for y in self.start.y..=self.end.y {
+                area.push((x, y));
+            }
+        }
 
-        Self {
-            start: Point {
-                x: start[0],
-                y: start[1],
-                z: start[2],
-            },
-            end: Point {
-                x: end[0],
-                y: end[1],
-                z: end[2],
-            },
-            id,
+        return area;
+    }
+
+    fn height(&self) -> u64 {
+        return self.end.z - self.start.z + 1;
+    }
+
+    fn cells(&self) -> Vec<(u64, u64, u64)> {
+        let mut cells = Vec::new();
+        for (x, y) in self.get_area() {
+            for z in self.start.z..=self.end.z {
+                cells.push((x, y, z));
+            }
         }
+
+        return cells;
     }
+}
 
-    fn get_area(&self) -> Vec<Point> {
-        if self.start.x != self.end.x {
-            return (self.start.x..=self.end.x)
-                .map(|x| Point {
-                    x,
-                    y: self.start.y,
-                    z: 1,
-                })
-                .collect();
+#[derive(Debug, PartialEq, Eq)]
+enum BrickError {
+    Malformed(usize),
+    Inverted(usize),
+    BelowGround(usize),
+    Overlap(usize, usize),
+}
+
+// Jenga::fall expects every brick to have its start at or below its end on
+// each axis, to be above the ground and not to share a cell with another.
+fn validate(bricks: &[Brick]) -> Result<(), BrickError> {
+    let mut occupied = HashMap::new();
+
+    for brick in bricks {
+        if brick.start.x > brick.end.x || brick.start.y > brick.end.y || brick.start.z > brick.end.z
+        {
+            return Err(BrickError::Inverted(brick.id));
         }
 
-        if self.start.y != self.end.y {
-            return (self.start.y..=self.end.y)
-                .map(|y| Point {
-                    x: self.start.x,
-                    y,
-                    z: 1,
-                })
-                .collect();
+        if brick.start.z < 1 {
+            return Err(BrickError::BelowGround(brick.id));
         }
 
-        return vec![Point {
-            x: self.start.x,
-            y: self.start.y,
-            z: self.end.z - self.start.z + 1,
-        }];
+        for cell in brick.cells() {
+            if let Some(other) = occupied.insert(cell, brick.id) {
+                return Err(BrickError::Overlap(other, brick.id));
+            }
+        }
     }
+
+    return Ok(());
 }
 
 struct Jenga {
-    grid: HashMap<(u64, u64), (u64, usize)>,
     supported_by: HashMap<usize, HashSet<usize>>,
     supports: HashMap<usize, Vec<usize>>,
+    // Bricks in the order they settled, so every brick comes after the
+    // bricks supporting it.
+    order: Vec<usize>,
+    // Where every brick came to rest.
+    bricks: HashMap<usize, Brick>,
+}
+
+// Dominator tree of the support graph rooted at the ground. Removing a brick
+// makes another one fall exactly when it dominates it, i.e. when every chain
+// of supports from the ground to that brick goes through it.
+struct DominatorTree {
+    ground: usize,
+    depth: Vec<usize>,
+    // ancestors[k][brick] is the 2^k-th dominator above the brick.
+    ancestors: Vec<Vec<usize>>,
+}
+
+impl DominatorTree {
+    fn new(jenga: &Jenga) -> Self {
+        let ground = jenga.order.iter().max().map_or(0, |id| id + 1);
+        let levels = (usize::BITS - ground.leading_zeros()).max(1) as usize;
+        let mut tree = DominatorTree {
+            ground,
+            depth: vec![0; ground + 1],
+            ancestors: vec![vec![ground; ground + 1]; levels],
+        };
+
+        for brick in &jenga.order {
+            let idom = jenga.supported_by[brick]
+                .iter()
+                .copied()
+                .reduce(|a, b| tree.lca(a, b))
+                .unwrap_or(ground);
+
+            tree.depth[*brick] = tree.depth[idom] + 1;
+            tree.ancestors[0][*brick] = idom;
+            for k in 1..levels {
+                tree.ancestors[k][*brick] = tree.ancestors[k - 1][tree.ancestors[k - 1][*brick]];
+            }
+        }
+
+        return tree;
+    }
+
+    fn idom(&self, brick: usize) -> usize {
+        return self.ancestors[0][brick];
+    }
+
+    fn lca(&self, mut a: usize, mut b: usize) -> usize {
+        if self.depth[a] < self.depth[b] {
+            (a, b) = (b, a);
+        }
+
+        for k in (0..self.ancestors.len()).rev() {
+            if self.depth[a] - self.depth[b] >= 1 << k {
+                a = self.ancestors[k][a];
+            }
+        }
+
+        if a == b {
+            return a;
+        }
+
+        for k in (0..self.ancestors.len()).rev() {
+            if self.ancestors[k][a] != self.ancestors[k][b] {
+                a = self.ancestors[k][a];
+                b = self.ancestors[k][b];
+            }
+        }
+
+        return self.idom(a);
+    }
 }
 
 impl Jenga {
@@ -88,41 +188,50 @@ impl Jenga {
         let mut grid = HashMap::new();
         let mut supported_by = HashMap::new();
         let mut supports = HashMap::new();
+        let mut order = Vec::new();
+        let mut settled = HashMap::new();
 
-        bricks.sort_by(|a, b| a.start.z.cmp(&b.start.z));
+        bricks.sort_by_key(|brick| brick.start.z);
 
-        for brick in bricks {
+        for mut brick in bricks {
             let area = brick.get_area();
+            let height = brick.height();
             supports.insert(brick.id, Vec::new());
+            order.push(brick.id);
 
             let mut max = 0;
             let mut brick_supported_by: HashSet<usize> = HashSet::new();
             for point in &area {
-                if let Some((height, id)) = grid.get(&(point.x, point.y)) {
-                    if max < *height {
-                        max = *height;
+                if let Some((top, id)) = grid.get(point) {
+                    if max < *top {
+                        max = *top;
                         brick_supported_by = HashSet::new();
                         brick_supported_by.insert(*id);
-                    } else if max == *height {
+                    } else if max == *top {
                         brick_supported_by.insert(*id);
                     }
                 }
             }
 
             for supported in &brick_supported_by {
-                supports.get_mut(&supported).unwrap().push(brick.id);
+                supports.get_mut(supported).unwrap().push(brick.id);
             }
             supported_by.insert(brick.id, brick_supported_by);
 
             for point in &area {
-                grid.insert((point.x, point.y), (max + point.z, brick.id));
+                grid.insert(*point, (max + height, brick.id));
             }
+
+            brick.start.z = max + 1;
+            brick.end.z = max + height;
+            settled.insert(brick.id, brick);
         }
 
         return Self {
-            grid,
             supported_by,
             supports,
+            order,
+            bricks: settled,
         };
     }
 
@@ -141,18 +250,124 @@ impl Jenga {
         return result;
     }
 
-    fn total_destruction(&mut self) -> usize {
-        let mut result = 0;
+    fn total_destruction(&self) -> usize {
+        return self.chain_reactions().values().sum();
+    }
+
+    // How many other bricks fall when each brick is removed on its own, which
+    // is the size of its subtree in the dominator tree.
+    fn chain_reactions(&self) -> HashMap<usize, usize> {
+        let tree = DominatorTree::new(self);
+        let mut subtree = vec![1; tree.ground + 1];
 
-        for (brick, _) in &self.supports {
-            result += self.fall_brick(*brick);
+        for brick in self.order.iter().rev() {
+            subtree[tree.idom(*brick)] += subtree[*brick];
         }
 
-        result
+        return self
+            .order
+            .iter()
+            .map(|brick| (*brick, subtree[*brick] - 1))
+            .collect();
     }
 
+    // Every brick that falls once the bricks in `removed` are taken out,
+    // not counting the removed bricks themselves.
+    fn falls_if_removed(&self, removed: &HashSet<usize>) -> HashSet<usize> {
+        let mut fallen = HashSet::new();
+
+        for brick in &self.order {
+            if removed.contains(brick) {
+                continue;
+            }
+
+            let supported_by = &self.supported_by[brick];
+            if !supported_by.is_empty()
+                && supported_by
+                    .iter()
+                    .all(|b| removed.contains(b) || fallen.contains(b))
+            {
+                fallen.insert(*brick);
+            }
+        }
+
+        return fallen;
+    }
+
+    // Takes the bricks out and lets everything above drop to its new
+    // resting height. Brick ids stay the same.
+    fn remove(&self, removed: &HashSet<usize>) -> Jenga {
+        let bricks = self
+            .order
+            .iter()
+            .filter(|id| !removed.contains(id))
+            .map(|id| self.bricks[id].clone())
+            .collect();
+
+        return Jenga::fall(bricks);
+    }
+
+    // Every brick as a box with its own object, one unit per cube.
+    fn to_obj(&self) -> String {
+        let mut obj = String::new();
+
+        for (i, id) in self.order.iter().enumerate() {
+            let brick = &self.bricks[id];
+            let (x0, y0, z0) = (brick.start.x, brick.start.y, brick.start.z);
+            let (x1, y1, z1) = (brick.end.x + 1, brick.end.y + 1, brick.end.z + 1);
+
+            obj.push_str(&format!("o brick_{}\n", id));
+            for (x, y, z) in [
+                (x0, y0, z0),
+                (x1, y0, z0),
+                (x1, y1, z0),
+                (x0, y1, z0),
+                (x0, y0, z1),
+                (x1, y0, z1),
+                (x1, y1, z1),
+                (x0, y1, z1),
+            ] {
+                obj.push_str(&format!("v {} {} {}\n", x, y, z));
+            }
+
+            let base = i * 8;
+            for face in [
+                [1, 4, 3, 2],
+                [5, 6, 7, 8],
+                [1, 2, 6, 5],
+                [2, 3, 7, 6],
+                [3, 4, 8, 7],
+                [4, 1, 5, 8],
+            ] {
+                let indices: Vec<String> = face.iter().map(|v| (base + v).to_string()).collect();
+                obj.push_str(&format!("f {}\n", indices.join(" ")));
+            }
+        }
+
+        return obj;
+    }
+
+    // One `x y z brick` line per occupied cell.
+    fn to_voxels(&self) -> String {
+        let mut voxels = String::new();
+
+        for id in &self.order {
+            let brick = &self.bricks[id];
+            for x in brick.start.x..=brick.end.x {
+                for y in brick.start.y..=brick.end.y {
+                    for z in brick.start.z..=brick.end.z {
+                        voxels.push_str(&format!("{} {} {} {}\n", x, y, z, id));
+                    }
+                }
+            }
+        }
+
+        return voxels;
+    }
+
+    #[cfg(test)]
     fn fall_brick(&self, brick: usize) -> usize {
-        let mut q = VecDeque::new();
+        let mut q = std::collections::VecDeque::new();
         q.push_back(brick);
         let mut fallen = HashSet::new();
         fallen.insert(&brick);
@@ -173,18 +388,368 @@ impl Jenga {
     }
 }
 
-fn main() {
-    let input = fs::read_to_string("input.txt").unwrap();
-    let bricks = input
+// Drops bricks one cell at a time until none of them can move any more.
+// Slow, but it makes no assumptions about the order bricks settle in.
+#[cfg(test)]
+fn settle_cell_by_cell(mut bricks: Vec<Brick>) -> Vec<Brick> {
+    let mut occupied: HashSet<(u64, u64, u64)> =
+        bricks.iter().flat_map(|brick| brick.cells()).collect();
+
+    let mut moved = true;
+    while moved {
+        moved = false;
+
+        for brick in bricks.iter_mut() {
+            let blocked = brick.start.z == 1
+                || brick
+                    .get_area()
+                    .iter()
+                    .any(|(x, y)| occupied.contains(&(*x, *y, brick.start.z - 1)));
+            if blocked {
+                continue;
+            }
+
+            for cell in brick.cells() {
+                occupied.remove(&cell);
+            }
+            brick.start.z -= 1;
+            brick.end.z -= 1;
+            occupied.extend(brick.cells());
+            moved = true;
+        }
+    }
+
+    return bricks;
+}
+
+fn parse_bricks(s: &str) -> Result<Vec<Brick>, BrickError> {
+    return s
         .lines()
         .enumerate()
-        .map(|(i, line)| Brick::parse(&line, i))
+        .map(|(i, line)| Brick::parse(line, i))
         .collect();
+}
+
+fn main() {
+    let input = fs::read_to_string("input.txt").unwrap();
+    let bricks = parse_bricks(&input).unwrap();
+    validate(&bricks).unwrap();
 
     let mut jenga = Jenga::fall(bricks);
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    let removing = args.first().is_some_and(|arg| arg == "remove");
+    if removing {
+        let removed: HashSet<usize> = args[1..]
+            .iter()
+            .filter(|arg| !arg.starts_with("--"))
+            .map(|id| id.parse().unwrap())
+            .collect();
+        let mut fallen: Vec<usize> = jenga.falls_if_removed(&removed).into_iter().collect();
+        fallen.sort();
+
+        println!("{} bricks fall: {:?}", fallen.len(), fallen);
+        jenga = jenga.remove(&removed);
+    }
+
+    if args.iter().any(|arg| arg == "--obj") {
+        fs::write("tower.obj", jenga.to_obj()).unwrap();
+    }
+
+    if args.iter().any(|arg| arg == "--voxels") {
+        fs::write("tower.txt", jenga.to_voxels()).unwrap();
+    }
+
+    if removing {
+        return;
+    }
+
     let dissolvable = jenga.dissolvable();
-    println!("{:?}", jenga.supports);
     println!("{}", dissolvable.len());
     println!("{}", jenga.total_destruction());
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs};
+
+    use crate::{parse_bricks, settle_cell_by_cell, validate, Brick, BrickError, Jenga, Point};
+
+    fn brick(id: usize, start: (u64, u64, u64), end: (u64, u64, u64)) -> Brick {
+        return Brick {
+            start: Point {
+                x: start.0,
+                y: start.1,
+                z: start.2,
+            },
+            end: Point {
+                x: end.0,
+                y: end.1,
+                z: end.2,
+            },
+            id,
+        };
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(
+            validate(&parse_bricks(&fs::read_to_string("input.txt").unwrap()).unwrap()),
+            Ok(())
+        );
+
+        assert_eq!(
+            validate(&[brick(0, (1, 0, 1), (0, 0, 1))]),
+            Err(BrickError::Inverted(0))
+        );
+        assert_eq!(
+            validate(&[brick(0, (0, 0, 3), (0, 0, 2))]),
+            Err(BrickError::Inverted(0))
+        );
+        assert_eq!(
+            validate(&[
+                brick(0, (0, 0, 1), (0, 0, 1)),
+                brick(1, (0, 0, 0), (2, 0, 0))
+            ]),
+            Err(BrickError::BelowGround(1))
+        );
+        assert_eq!(
+            validate(&[
+                brick(0, (0, 0, 1), (0, 0, 4)),
+                brick(1, (0, 1, 3), (0, 0, 3))
+            ]),
+            Err(BrickError::Inverted(1))
+        );
+        assert_eq!(
+            validate(&[
+                brick(0, (0, 0, 1), (0, 0, 4)),
+                brick(1, (0, 0, 3), (0, 2, 3))
+            ]),
+            Err(BrickError::Overlap(0, 1))
+        );
+    }
+
+    #[test]
+    fn parse_malformed_bricks() {
+        assert_eq!(
+            parse_bricks("1,0,1~1,2,1\n0,0,2~2,0,2"),
+            Ok(vec![
+                brick(0, (1, 0, 1), (1, 2, 1)),
+                brick(1, (0, 0, 2), (2, 0, 2))
+            ])
+        );
+
+        for line in [
+            "1,0,1",
+            "1,0~1,2,1",
+            "1,0,1~1,2,1,4",
+            "1,0,x~1,2,1",
+            "1,0,-1~1,2,1",
+        ] {
+            assert_eq!(
+                parse_bricks(&format!("0,0,1~0,0,1\n{}", line)),
+                Err(BrickError::Malformed(1))
+            );
+        }
+    }
+
+    fn random_bricks(next: &mut impl FnMut() -> u64, count: usize) -> Vec<Brick> {
+        let mut bricks = Vec::new();
+
+        while bricks.len() < count {
+            let (x, y, z) = (next() % 4, next() % 4, 1 + next() % 20);
+            let length = next() % 3;
+            let end = match next() % 4 {
+                0 => (x + length, y, z),
+                1 => (x, y + length, z),
+                2 => (x, y, z + length),
+                _ => (x + length, y + next() % 2, z),
+            };
+
+            let candidate = brick(bricks.len(), (x, y, z), end);
+            bricks.push(candidate);
+            if validate(&bricks).is_err() {
+                bricks.pop();
+            }
+        }
+
+        return bricks;
+    }
+
+    #[test]
+    fn fall_matches_cell_by_cell() {
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            return state;
+        };
+
+        for _ in 0..200 {
+            let bricks = random_bricks(&mut next, 12);
+            let jenga = Jenga::fall(bricks.clone());
+            let reference = settle_cell_by_cell(bricks);
+
+            for settled in &reference {
+                assert_eq!(&jenga.bricks[&settled.id], settled);
+
+                let supported_by: HashSet<usize> = reference
+                    .iter()
+                    .filter(|below| below.end.z + 1 == settled.start.z)
+                    .filter(|below| {
+                        let area = below.get_area();
+                        settled.get_area().iter().any(|cell| area.contains(cell))
+                    })
+                    .map(|below| below.id)
+                    .collect();
+                assert_eq!(jenga.supported_by[&settled.id], supported_by);
+            }
+        }
+    }
+
+    #[test]
+    fn input_matches_cell_by_cell() {
+        let bricks = parse_bricks(&fs::read_to_string("input.txt").unwrap()).unwrap();
+        let jenga = Jenga::fall(bricks.clone());
+
+        for settled in settle_cell_by_cell(bricks) {
+            assert_eq!(jenga.bricks[&settled.id], settled);
+        }
+    }
+
+    fn settle(file: &str) -> Jenga {
+        return Jenga::fall(parse_bricks(&fs::read_to_string(file).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn example() {
+        let jenga = settle("test.txt");
+
+        assert_eq!(jenga.dissolvable().len(), 5);
+        assert_eq!(jenga.total_destruction(), 7);
+        assert_eq!(jenga.chain_reactions()[&0], 6);
+        assert_eq!(jenga.chain_reactions()[&5], 1);
+    }
+
+    #[test]
+    fn chain_reactions_match_bfs() {
+        let jenga = settle("input.txt");
+
+        for (brick, count) in jenga.chain_reactions() {
+            assert_eq!(count, jenga.fall_brick(brick), "brick {}", brick);
+        }
+    }
+
+    #[test]
+    fn falls_if_removed() {
+        let jenga = settle("test.txt");
+        let removed = |bricks: &[usize]| -> Vec<usize> {
+            let mut fallen: Vec<usize> = jenga
+                .falls_if_removed(&bricks.iter().copied().collect())
+                .into_iter()
+                .collect();
+            fallen.sort();
+            return fallen;
+        };
+
+        assert_eq!(removed(&[]), Vec::<usize>::new());
+        assert_eq!(removed(&[0]), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(removed(&[3]), Vec::<usize>::new());
+        assert_eq!(removed(&[3, 4]), vec![5, 6]);
+        assert_eq!(removed(&[1, 2]), vec![3, 4, 5, 6]);
+        assert_eq!(removed(&[6, 0]), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn settled_positions() {
+        let jenga = settle("test.txt");
+        let bottoms: Vec<u64> = (0..7).map(|id| jenga.bricks[&id].start.z).collect();
+        assert_eq!(bottoms, vec![1, 2, 2, 3, 3, 4, 5]);
+        assert_eq!(jenga.bricks[&6].end.z, 6);
+    }
+
+    #[test]
+    fn remove_resettles() {
+        let jenga = settle("test.txt");
+
+        let removed = jenga.remove(&HashSet::from([0]));
+        assert_eq!(removed.bricks.len(), 6);
+        assert!(!removed.bricks.contains_key(&0));
+        for id in 1..7 {
+            assert_eq!(removed.bricks[&id].start.z, jenga.bricks[&id].start.z - 1);
+        }
+
+        let removed = jenga.remove(&HashSet::from([3]));
+        for id in [1, 2, 4, 5, 6] {
+            assert_eq!(removed.bricks[&id], jenga.bricks[&id]);
+        }
+        assert_eq!(removed.dissolvable().len(), 3);
+    }
+
+    #[test]
+    fn moved_bricks_are_the_fallen_ones() {
+        let jenga = settle("input.txt");
+        let mut state = 0x9e3779b97f4a7c15u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            return state as usize;
+        };
+
+        for _ in 0..20 {
+            let removed: HashSet<usize> = (0..1 + next() % 5)
+                .map(|_| next() % jenga.order.len())
+                .collect();
+            let resettled = jenga.remove(&removed);
+
+            let moved: HashSet<usize> = resettled
+                .bricks
+                .values()
+                .filter(|brick| brick != &&jenga.bricks[&brick.id])
+                .map(|brick| brick.id)
+                .collect();
+            assert_eq!(moved, jenga.falls_if_removed(&removed));
+
+            let again = Jenga::fall(resettled.bricks.values().cloned().collect::<Vec<Brick>>());
+            assert_eq!(again.bricks, resettled.bricks);
+        }
+    }
+
+    #[test]
+    fn exports() {
+        let jenga = settle("test.txt");
+
+        let obj = jenga.to_obj();
+        assert_eq!(obj.lines().filter(|line| line.starts_with("o ")).count(), 7);
+        assert_eq!(
+            obj.lines().filter(|line| line.starts_with("v ")).count(),
+            56
+        );
+        assert_eq!(
+            obj.lines().filter(|line| line.starts_with("f ")).count(),
+            42
+        );
+        assert!(obj.starts_with("o brick_0\nv 1 0 1\nv 2 0 1\nv 2 3 1\nv 1 3 1\n"));
+        assert!(obj.ends_with("f 52 49 53 56\n"));
+
+        let voxels = jenga.to_voxels();
+        assert_eq!(voxels.lines().count(), 3 * 6 + 2);
+        assert!(voxels.starts_with("1 0 1 0\n1 1 1 0\n1 2 1 0\n"));
+        assert!(voxels.ends_with("1 1 5 6\n1 1 6 6\n"));
+    }
+
+    #[test]
+    fn single_removals_match_chain_reactions() {
+        let jenga = settle("input.txt");
+        let chain_reactions = jenga.chain_reactions();
+
+        for (brick, count) in chain_reactions.iter().take(200) {
+            assert_eq!(
+                jenga.falls_if_removed(&HashSet::from([*brick])).len(),
+                *count
+            );
+        }
+    }
+}
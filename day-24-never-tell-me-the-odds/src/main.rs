@@ -1,9 +1,11 @@
-use std::fs;
+#![allow(clippy::needless_return)]
+
+use std::{cmp::Ordering, collections::BTreeSet, env, fmt, fs, process};
 
 struct Point {
-    x: f64,
-    y: f64,
-    z: f64,
+    x: i64,
+    y: i64,
+    z: i64,
 }
 
 impl Point {
@@ -11,11 +13,126 @@ impl Point {
         let split = s.split(", ").collect::<Vec<_>>();
 
         Point {
-            x: split[0].trim().parse::<i64>().unwrap() as f64,
-            y: split[1].trim().parse::<i64>().unwrap() as f64,
-            z: split[2].trim().parse::<i64>().unwrap() as f64,
+            x: split[0].trim().parse::<i64>().unwrap(),
+            y: split[1].trim().parse::<i64>().unwrap(),
+            z: split[2].trim().parse::<i64>().unwrap(),
         }
     }
+
+    fn coordinates(&self) -> [i128; 3] {
+        return [self.x as i128, self.y as i128, self.z as i128];
+    }
+}
+
+fn dot(a: [i128; 3], b: [i128; 3]) -> i128 {
+    return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+}
+
+fn checked_dot(a: [i128; 3], b: [i128; 3]) -> Option<i128> {
+    return a[0]
+        .checked_mul(b[0])?
+        .checked_add(a[1].checked_mul(b[1])?)?
+        .checked_add(a[2].checked_mul(b[2])?);
+}
+
+// Squared distances between stones far apart, or far along a long window,
+// do not fit in an i128.
+#[derive(Debug, PartialEq, Eq)]
+struct Overflow;
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        return a.abs();
+    }
+
+    return gcd(b, a % b);
+}
+
+// An exact fraction, always kept in lowest terms with a positive denominator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rational {
+    numerator: i128,
+    denominator: i128,
+}
+
+impl Rational {
+    fn new(numerator: i128, denominator: i128) -> Self {
+        assert!(denominator != 0, "Rational with a zero denominator");
+
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+        return Rational {
+            numerator: numerator / divisor,
+            denominator: denominator / divisor,
+        };
+    }
+
+    fn integer(value: i128) -> Self {
+        return Rational::new(value, 1);
+    }
+
+    fn is_negative(&self) -> bool {
+        return self.numerator < 0;
+    }
+
+    fn to_f64(self) -> f64 {
+        return self.numerator as f64 / self.denominator as f64;
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return (self.numerator * other.denominator).cmp(&(other.numerator * self.denominator));
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.denominator == 1 {
+            return write!(f, "{}", self.numerator);
+        }
+
+        return write!(f, "{}/{}", self.numerator, self.denominator);
+    }
+}
+
+// Where two XY paths cross, and when each stone gets there. A negative time
+// means the stone was there in the past.
+#[derive(Debug, PartialEq, Eq)]
+struct Intersection {
+    x: Rational,
+    y: Rational,
+    t1: Rational,
+    t2: Rational,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Crossing {
+    Parallel,
+    // Both stones move along the same line.
+    Coincident,
+    InPast(Intersection),
+    OutsideWindow(Intersection),
+    InWindow(Intersection),
+}
+
+// The moment two stones are closest within a time window, with the squared
+// distance between them kept exact.
+#[derive(Debug, PartialEq, Eq)]
+struct Approach {
+    time: Rational,
+    distance_squared: Rational,
+}
+
+impl Approach {
+    fn distance(&self) -> f64 {
+        return self.distance_squared.to_f64().sqrt();
+    }
 }
 
 struct Hail {
@@ -33,37 +150,162 @@ impl Hail {
         }
     }
 
-    fn intersect(&self, other: &Hail) -> Option<(f64, f64)> {
-        let determinant = self.velocity.y * other.velocity.x - self.velocity.x * other.velocity.y;
-        if determinant == 0. {
+    // Crossing point of the XY projections of both paths, at any time.
+    fn intersect(&self, other: &Hail) -> Option<Intersection> {
+        let (vx1, vy1) = (self.velocity.x as i128, self.velocity.y as i128);
+        let (vx2, vy2) = (other.velocity.x as i128, other.velocity.y as i128);
+
+        let determinant = vy1 * vx2 - vx1 * vy2;
+        if determinant == 0 {
             return None;
         }
 
-        let b1 = other.position.x - self.position.x;
-        let b2 = other.position.y - self.position.y;
+        let b1 = (other.position.x - self.position.x) as i128;
+        let b2 = (other.position.y - self.position.y) as i128;
+
+        let t1 = vx2 * b2 - vy2 * b1;
+        let t2 = vx1 * b2 - vy1 * b1;
+
+        return Some(Intersection {
+            x: Rational::new(
+                self.position.x as i128 * determinant + t1 * vx1,
+                determinant,
+            ),
+            y: Rational::new(
+                self.position.y as i128 * determinant + t1 * vy1,
+                determinant,
+            ),
+            t1: Rational::new(t1, determinant),
+            t2: Rational::new(t2, determinant),
+        });
+    }
 
-        let t1 = (other.velocity.x * b2 - other.velocity.y * b1) / determinant;
-        let t2 = (self.velocity.x * b2 - self.velocity.y * b1) / determinant;
+    // Saturates instead of overflowing, which still gives bounds on where
+    // the stone is, since saturating keeps the order of positions.
+    fn position_at(&self, time: i128) -> [i128; 3] {
+        let position = self.position.coordinates();
+        let velocity = self.velocity.coordinates();
 
-        if t1 < 0. || t2 < 0. {
+        return [0, 1, 2].map(|i| position[i].saturating_add(velocity[i].saturating_mul(time)));
+    }
+
+    // The first moment, now or later, when both stones are in the same place.
+    fn collision_time(&self, other: &Hail) -> Option<Rational> {
+        let offset =
+            [0, 1, 2].map(|i| other.position.coordinates()[i] - self.position.coordinates()[i]);
+        let closing =
+            [0, 1, 2].map(|i| self.velocity.coordinates()[i] - other.velocity.coordinates()[i]);
+
+        let speed_squared = dot(closing, closing);
+        if speed_squared == 0 {
+            return if offset == [0, 0, 0] {
+                Some(Rational::integer(0))
+            } else {
+                None
+            };
+        }
+
+        // The offset has to be a multiple of the closing velocity.
+        let time = Rational::new(dot(offset, closing), speed_squared);
+        if time.is_negative()
+            || (0..3).any(|i| offset[i] * time.denominator != closing[i] * time.numerator)
+        {
             return None;
         }
 
-        return Some((
-            self.position.x + t1 * self.velocity.x,
-            self.position.y + t1 * self.velocity.y,
-        ));
+        return Some(time);
+    }
+
+    // Closest approach between `start` and `end`, both inclusive.
+    fn closest_approach(&self, other: &Hail, start: i64, end: i64) -> Result<Approach, Overflow> {
+        let (start, end) = (start as i128, end as i128);
+        let offset =
+            [0, 1, 2].map(|i| self.position.coordinates()[i] - other.position.coordinates()[i]);
+        let drift =
+            [0, 1, 2].map(|i| self.velocity.coordinates()[i] - other.velocity.coordinates()[i]);
+
+        let at = |time: i128| {
+            let mut between = offset;
+            for (value, drift) in between.iter_mut().zip(drift) {
+                *value = drift
+                    .checked_mul(time)
+                    .and_then(|moved| value.checked_add(moved))
+                    .ok_or(Overflow)?;
+            }
+
+            return Ok(Approach {
+                time: Rational::integer(time),
+                distance_squared: Rational::integer(checked_dot(between, between).ok_or(Overflow)?),
+            });
+        };
+
+        let speed_squared = checked_dot(drift, drift).ok_or(Overflow)?;
+        if speed_squared == 0 {
+            return at(start);
+        }
+
+        // Unconstrained minimum of |offset + drift * t|^2, at
+        // t = -projection / speed_squared.
+        let projection = checked_dot(offset, drift).ok_or(Overflow)?;
+        let time = projection.checked_neg().ok_or(Overflow)?;
+        if time < start.checked_mul(speed_squared).ok_or(Overflow)? {
+            return at(start);
+        }
+        if time > end.checked_mul(speed_squared).ok_or(Overflow)? {
+            return at(end);
+        }
+
+        let distance_squared = checked_dot(offset, offset)
+            .and_then(|length| length.checked_mul(speed_squared))
+            .and_then(|length| length.checked_sub(projection.checked_mul(projection)?))
+            .ok_or(Overflow)?;
+        return Ok(Approach {
+            time: Rational::new(time, speed_squared),
+            distance_squared: Rational::new(distance_squared, speed_squared),
+        });
+    }
+
+    fn classify(&self, other: &Hail, min: i64, max: i64) -> Crossing {
+        let intersection = match self.intersect(other) {
+            Some(intersection) => intersection,
+            None => {
+                let dx = (other.position.x - self.position.x) as i128;
+                let dy = (other.position.y - self.position.y) as i128;
+                if dx * self.velocity.y as i128 == dy * self.velocity.x as i128 {
+                    return Crossing::Coincident;
+                }
+
+                return Crossing::Parallel;
+            }
+        };
+
+        if intersection.t1.is_negative() || intersection.t2.is_negative() {
+            return Crossing::InPast(intersection);
+        }
+
+        let (min, max) = (
+            Rational::integer(min as i128),
+            Rational::integer(max as i128),
+        );
+        if intersection.x < min
+            || intersection.x > max
+            || intersection.y < min
+            || intersection.y > max
+        {
+            return Crossing::OutsideWindow(intersection);
+        }
+
+        return Crossing::InWindow(intersection);
     }
 }
 
-fn count_intersect(hails: &Vec<Hail>, min: f64, max: f64) -> u64 {
+// Coincident paths are not counted, since they don't cross at a single point.
+fn count_intersect(hails: &[Hail], min: i64, max: i64) -> u64 {
     let mut count = 0;
     for i in 0..hails.len() - 1 {
         for j in i + 1..hails.len() {
-            if let Some((x, y)) = hails[i].intersect(&hails[j]) {
-                if x >= min && x <= max && y >= min && y <= max {
-                    count += 1;
-                }
+            if let Crossing::InWindow(_) = hails[i].classify(&hails[j], min, max) {
+                count += 1;
             }
         }
     }
@@ -71,16 +313,159 @@ fn count_intersect(hails: &Vec<Hail>, min: f64, max: f64) -> u64 {
     count
 }
 
+// A limit that does not fit in an i128 is above any squared distance.
+fn within(approach: &Approach, radius: i64) -> bool {
+    let radius = radius as i128;
+    return radius
+        .checked_mul(radius)
+        .and_then(|limit| limit.checked_mul(approach.distance_squared.denominator))
+        .is_none_or(|limit| approach.distance_squared.numerator <= limit);
+}
+
+// Pairs of stones whose boxes, grown by the radius, overlap between `start`
+// and `end`. Stones move in straight lines, so each one stays inside the box
+// spanned by its positions at both ends of the window. The boxes are found by
+// sweeping along x.
+fn overlapping_boxes(hails: &[Hail], radius: i64, start: i64, end: i64) -> Vec<(usize, usize)> {
+    let radius = radius as i128;
+    let boxes: Vec<([i128; 3], [i128; 3])> = hails
+        .iter()
+        .map(|hail| {
+            let first = hail.position_at(start as i128);
+            let last = hail.position_at(end as i128);
+            (
+                [0, 1, 2].map(|i| first[i].min(last[i]).saturating_sub(radius)),
+                [0, 1, 2].map(|i| first[i].max(last[i]).saturating_add(radius)),
+            )
+        })
+        .collect();
+
+    let mut order: Vec<usize> = (0..hails.len()).collect();
+    order.sort_by_key(|i| boxes[*i].0[0]);
+
+    let mut pairs = Vec::new();
+    let mut active: Vec<usize> = Vec::new();
+    for i in order {
+        let (low, high) = boxes[i];
+        active.retain(|j| boxes[*j].1[0] >= low[0]);
+
+        for &j in &active {
+            let (other_low, other_high) = boxes[j];
+            if (1..3).any(|axis| other_high[axis] < low[axis] || high[axis] < other_low[axis]) {
+                continue;
+            }
+
+            pairs.push((i.min(j), i.max(j)));
+        }
+
+        active.push(i);
+    }
+
+    return pairs;
+}
+
+// How often a window is halved at most, so stones that stay close for the
+// whole window cost at most 2^MAX_SLAB_DEPTH sweeps.
+const MAX_SLAB_DEPTH: u32 = 10;
+
+// Over a long window every box grows long enough to overlap most others,
+// which makes the sweep check close to every pair. A window with more
+// overlapping pairs than stones is split into two time slabs that are swept
+// on their own, and a pair is a candidate when it overlaps in any slab.
+fn candidate_pairs(
+    hails: &[Hail],
+    radius: i64,
+    (start, end): (i64, i64),
+    depth: u32,
+    candidates: &mut BTreeSet<(usize, usize)>,
+) {
+    let pairs = overlapping_boxes(hails, radius, start, end);
+    let length = end as i128 - start as i128;
+    if pairs.len() <= hails.len() || length <= 1 || depth == MAX_SLAB_DEPTH {
+        candidates.extend(pairs);
+        return;
+    }
+
+    let middle = (start as i128 + length / 2) as i64;
+    candidate_pairs(hails, radius, (start, middle), depth + 1, candidates);
+    candidate_pairs(hails, radius, (middle, end), depth + 1, candidates);
+}
+
+// Pairs of stones that come within `radius` of each other between `start`
+// and `end`. Only candidate pairs are checked exactly.
+fn pairs_within(
+    hails: &[Hail],
+    radius: i64,
+    start: i64,
+    end: i64,
+) -> Result<Vec<(usize, usize, Approach)>, Overflow> {
+    let mut candidates = BTreeSet::new();
+    candidate_pairs(hails, radius, (start, end), 0, &mut candidates);
+
+    let mut pairs = Vec::new();
+    for (a, b) in candidates {
+        let approach = hails[a].closest_approach(&hails[b], start, end)?;
+        if within(&approach, radius) {
+            pairs.push((a, b, approach));
+        }
+    }
+
+    return Ok(pairs);
+}
+
+// The radius and time window of the `near` command.
+fn parse_near(args: &[String]) -> Option<(i64, i64, i64)> {
+    if args.len() != 3 {
+        return None;
+    }
+
+    let radius: i64 = args[0].parse().ok()?;
+    let start: i64 = args[1].parse().ok()?;
+    let end: i64 = args[2].parse().ok()?;
+    if radius < 0 || start > end {
+        return None;
+    }
+
+    return Some((radius, start, end));
+}
+
 fn main() {
     let input = fs::read_to_string("input.txt").unwrap();
-    let hails = input
-        .lines()
-        .map(|line| Hail::parse(&line))
-        .collect::<Vec<_>>();
+    let hails = input.lines().map(Hail::parse).collect::<Vec<_>>();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "near") {
+        let Some((radius, start, end)) = parse_near(&args[1..]) else {
+            eprintln!("usage: near <radius> <start> <end>, with radius >= 0 and start <= end");
+            process::exit(1);
+        };
+
+        let window = Rational::integer(start as i128)..=Rational::integer(end as i128);
+        let Ok(pairs) = pairs_within(&hails, radius, start, end) else {
+            eprintln!("near: the distances in this window do not fit in 128 bits");
+            process::exit(1);
+        };
+        for (a, b, approach) in &pairs {
+            match hails[*a].collision_time(&hails[*b]) {
+                Some(time) if window.contains(&time) => {
+                    println!("{} {} collide at t={}", a, b, time)
+                }
+                _ => println!(
+                    "{} {} at t={} distance {:.3}",
+                    a,
+                    b,
+                    approach.time,
+                    approach.distance()
+                ),
+            }
+        }
+        println!("{} pairs", pairs.len());
+        return;
+    }
 
     println!(
         "{}",
-        count_intersect(&hails, 200000000000000., 400000000000000.)
+        count_intersect(&hails, 200000000000000, 400000000000000)
     );
 
     //Part 2 solved by hand (using Octave)
@@ -101,103 +486,297 @@ fn main() {
 mod tests {
     use std::fs;
 
-    use crate::{count_intersect, Hail, Point};
+    use crate::{
+        count_intersect, pairs_within, parse_near, within, Crossing, Hail, Overflow, Point,
+        Rational,
+    };
 
-    #[test]
-    fn intersect() {
-        let hail1 = Hail {
+    fn hail(position: (i64, i64, i64), velocity: (i64, i64, i64)) -> Hail {
+        return Hail {
             position: Point {
-                x: 20.,
-                y: 25.,
-                z: 34.,
+                x: position.0,
+                y: position.1,
+                z: position.2,
             },
             velocity: Point {
-                x: -2.,
-                y: -2.,
-                z: -4.,
+                x: velocity.0,
+                y: velocity.1,
+                z: velocity.2,
             },
         };
+    }
 
-        let hail2 = Hail {
-            position: Point {
-                x: 12.,
-                y: 31.,
-                z: 28.,
-            },
-            velocity: Point {
-                x: -1.,
-                y: -2.,
-                z: -1.,
-            },
-        };
+    #[test]
+    fn intersect() {
+        let hail1 = hail((20, 25, 34), (-2, -2, -4));
+        let hail2 = hail((12, 31, 28), (-1, -2, -1));
 
         let intersect = hail1.intersect(&hail2);
 
         assert!(intersect.is_some());
-        assert_eq!(intersect.unwrap(), (-2., 3.));
+        let intersect = intersect.unwrap();
+        assert_eq!(
+            (intersect.x, intersect.y),
+            (Rational::integer(-2), Rational::integer(3))
+        );
+        assert_eq!(
+            (intersect.t1, intersect.t2),
+            (Rational::integer(11), Rational::integer(14))
+        );
+        assert!(matches!(
+            hail1.classify(&hail2, 7, 27),
+            Crossing::OutsideWindow(_)
+        ));
 
-        let hail3 = Hail {
-            position: Point {
-                x: 18.,
-                y: 19.,
-                z: 22.,
-            },
-            velocity: Point {
-                x: -1.,
-                y: -1.,
-                z: -2.,
-            },
-        };
+        let hail3 = hail((18, 19, 22), (-1, -1, -2));
 
         //These two never intersect
-        let intersect = hail1.intersect(&hail3);
-        assert!(intersect.is_none());
+        assert!(hail1.intersect(&hail3).is_none());
+        assert_eq!(hail1.classify(&hail3, 7, 27), Crossing::Parallel);
 
-        let hail4 = Hail {
-            position: Point {
-                x: 20.,
-                y: 19.,
-                z: 15.,
-            },
-            velocity: Point {
-                x: 1.,
-                y: -5.,
-                z: 3.,
-            },
-        };
+        let hail4 = hail((20, 19, 15), (1, -5, 3));
         //These two intersect in the past for both
-        let intersect = hail3.intersect(&hail4);
-        assert!(intersect.is_none());
+        let crossing = hail3.classify(&hail4, 7, 27);
+        assert!(
+            matches!(crossing, Crossing::InPast(ref i) if i.t1.is_negative() && i.t2.is_negative())
+        );
 
         // These two intersect in the past for hail4
-        let intersect = hail1.intersect(&hail4);
-        assert!(intersect.is_none());
+        let crossing = hail1.classify(&hail4, 7, 27);
+        assert!(
+            matches!(crossing, Crossing::InPast(ref i) if !i.t1.is_negative() && i.t2.is_negative())
+        );
 
-        let hail5 = Hail {
-            position: Point {
-                x: 19.,
-                y: 13.,
-                z: 30.,
-            },
-            velocity: Point {
-                x: -2.,
-                y: 1.,
-                z: -2.,
-            },
-        };
+        let hail5 = hail((19, 13, 30), (-2, 1, -2));
         //These two intersect in the past for hail 4
-        let intersect = hail4.intersect(&hail5);
-        assert!(intersect.is_none())
+        let crossing = hail4.classify(&hail5, 7, 27);
+        assert!(matches!(crossing, Crossing::InPast(_)));
+
+        let crossing = hail5.classify(&hail3, 7, 27);
+        assert_eq!(
+            crossing,
+            Crossing::InWindow(crate::Intersection {
+                x: Rational::new(43, 3),
+                y: Rational::new(46, 3),
+                t1: Rational::new(7, 3),
+                t2: Rational::new(11, 3),
+            })
+        );
+    }
+
+    #[test]
+    fn coincident() {
+        let hail1 = hail((0, 0, 0), (1, 1, 0));
+        let hail2 = hail((2, 2, 5), (3, 3, 1));
+        let hail3 = hail((2, 3, 0), (3, 3, 1));
+
+        assert_eq!(hail1.classify(&hail2, 0, 10), Crossing::Coincident);
+        assert_eq!(hail1.classify(&hail3, 0, 10), Crossing::Parallel);
+    }
+
+    #[test]
+    fn exact_window_bounds() {
+        let min = 200000000000000;
+        let max = 400000000000000;
+        let horizontal = hail((100000000000000, 300000000000000, 0), (1, 0, 0));
+
+        // Crosses exactly on the lower bound.
+        let on_bound = hail((min, 299999999999997, 0), (0, 1, 0));
+        let crossing = horizontal.classify(&on_bound, min, max);
+        assert!(
+            matches!(crossing, Crossing::InWindow(ref i) if i.x == Rational::integer(min as i128))
+        );
+
+        // Crosses a third of a unit below the lower bound.
+        let below_bound = hail((min - 1, 299999999999998, 0), (1, 3, 0));
+        let crossing = horizontal.classify(&below_bound, min, max);
+        assert!(matches!(
+            crossing,
+            Crossing::OutsideWindow(ref i)
+                if i.x == Rational::new(3 * min as i128 - 1, 3) && i.t2 == Rational::new(2, 3)
+        ));
+    }
+
+    #[test]
+    fn collision_time() {
+        let input = fs::read_to_string("test.txt").unwrap();
+        let hails = input.lines().map(Hail::parse).collect::<Vec<_>>();
+        let rock = hail((24, 13, 10), (-3, 1, 2));
+
+        let times: Vec<Rational> = hails
+            .iter()
+            .map(|hail| rock.collision_time(hail).unwrap())
+            .collect();
+        assert_eq!(times, [5, 3, 4, 6, 1].map(Rational::integer));
+
+        // Same path at different speeds, but they only met in the past.
+        let a = hail((0, 0, 0), (2, 0, 0));
+        let b = hail((-3, 0, 0), (3, 0, 0));
+        assert_eq!(a.collision_time(&b), Some(Rational::integer(3)));
+        assert_eq!(b.collision_time(&hail((0, 0, 0), (4, 0, 0))), None);
+
+        // Collide at half a nanosecond.
+        let c = hail((1, 1, 0), (-1, -1, 0));
+        assert_eq!(
+            hail((0, 0, 0), (1, 1, 0)).collision_time(&c),
+            Some(Rational::new(1, 2))
+        );
+
+        // Their XY paths cross, but they reach the crossing at different
+        // times.
+        assert_eq!(hails[0].collision_time(&hails[1]), None);
+        assert_eq!(
+            a.collision_time(&hail((0, 0, 0), (2, 0, 0))),
+            Some(Rational::integer(0))
+        );
+    }
+
+    #[test]
+    fn closest_approach() {
+        let a = hail((0, 0, 0), (1, 0, 0));
+        let b = hail((10, 3, 4), (-1, 0, 0));
+
+        let approach = a.closest_approach(&b, 0, 100).unwrap();
+        assert_eq!(approach.time, Rational::integer(5));
+        assert_eq!(approach.distance_squared, Rational::integer(25));
+        assert_eq!(approach.distance(), 5.);
+
+        let approach = a.closest_approach(&b, 0, 2).unwrap();
+        assert_eq!(approach.time, Rational::integer(2));
+        assert_eq!(approach.distance_squared, Rational::integer(36 + 25));
+
+        let approach = a.closest_approach(&b, 8, 20).unwrap();
+        assert_eq!(approach.time, Rational::integer(8));
+
+        let c = hail((0, 1, 0), (0, 0, 1));
+        let approach = a.closest_approach(&c, 0, 10).unwrap();
+        assert_eq!(approach.time, Rational::new(0, 1));
+        assert_eq!(approach.distance_squared, Rational::integer(1));
+
+        let d = hail((3, 1, -2), (0, 0, 1));
+        let approach = a.closest_approach(&d, 0, 10).unwrap();
+        assert_eq!(approach.time, Rational::new(5, 2));
+        assert_eq!(approach.distance_squared, Rational::new(3, 2));
+        assert!(within(&approach, 2));
+        assert!(!within(&approach, 1));
+        assert!(within(&approach, i64::MAX));
+
+        let e = hail((0, 0, 0), (1 << 62, 0, 0));
+        let approach = e.closest_approach(&hail((0, 0, 0), (0, 0, 0)), 1, 10);
+        assert_eq!(
+            approach.unwrap().distance_squared,
+            Rational::integer(1 << 124)
+        );
+        assert_eq!(
+            e.closest_approach(&hail((0, 0, 0), (0, 0, 0)), 4, 10),
+            Err(Overflow)
+        );
+        assert_eq!(
+            a.closest_approach(&b, 0, i64::MAX).unwrap().time,
+            Rational::integer(5)
+        );
+        assert_eq!(
+            a.closest_approach(&b, i64::MAX - 1, i64::MAX),
+            Err(Overflow)
+        );
+    }
+
+    #[test]
+    fn sweep_matches_all_pairs() {
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut next = |range: i64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            return (state % (2 * range as u64 + 1)) as i64 - range;
+        };
+
+        for _ in 0..20 {
+            let hails: Vec<Hail> = (0..200)
+                .map(|_| {
+                    hail(
+                        (next(1000), next(1000), next(1000)),
+                        (next(20), next(20), next(20)),
+                    )
+                })
+                .collect();
+            let radius = next(50).abs() + 1;
+            let start = next(10).abs();
+
+            // Long windows are split into time slabs.
+            for end in [10 + next(40).abs(), 100 + next(2000).abs()] {
+                let mut expected = Vec::new();
+                for a in 0..hails.len() {
+                    for b in a + 1..hails.len() {
+                        let approach = hails[a].closest_approach(&hails[b], start, end).unwrap();
+                        if within(&approach, radius) {
+                            expected.push((a, b, approach));
+                        }
+                    }
+                }
+
+                assert_eq!(pairs_within(&hails, radius, start, end), Ok(expected));
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture`"]
+    fn bench_pairs_within() {
+        let mut state = 0x9e3779b97f4a7c15u64;
+        let mut next = |range: i64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            return (state % (2 * range as u64 + 1)) as i64 - range;
+        };
+
+        let hails: Vec<Hail> = (0..50_000)
+            .map(|_| {
+                hail(
+                    (
+                        next(1_000_000_000),
+                        next(1_000_000_000),
+                        next(1_000_000_000),
+                    ),
+                    (next(500), next(500), next(500)),
+                )
+            })
+            .collect();
+
+        let start = std::time::Instant::now();
+        let pairs = pairs_within(&hails, 1_000_000, 0, 10_000).unwrap();
+        println!("{} pairs in {:?}", pairs.len(), start.elapsed());
+    }
+
+    #[test]
+    fn rational() {
+        assert_eq!(Rational::new(4, -6), Rational::new(-2, 3));
+        assert_eq!(Rational::new(0, -5), Rational::integer(0));
+        assert!(Rational::new(1, 3) < Rational::new(1, 2));
+        assert!(Rational::new(-1, 2) < Rational::new(-1, 3));
+        assert_eq!(Rational::new(-6, 4).to_string(), "-3/2");
+        assert_eq!(Rational::new(8, 4).to_string(), "2");
     }
 
     #[test]
     fn test_count_intersect() {
         let input = fs::read_to_string("test.txt").unwrap();
-        let hails = input
-            .lines()
-            .map(|line| Hail::parse(&line))
-            .collect::<Vec<_>>();
+        let hails = input.lines().map(Hail::parse).collect::<Vec<_>>();
+
+        assert_eq!(count_intersect(&hails, 7, 27), 2);
+    }
+
+    #[test]
+    fn near_arguments() {
+        let args = |s: &str| -> Vec<String> { s.split(' ').map(String::from).collect() };
 
-        assert_eq!(count_intersect(&hails, 7., 27.), 2);
+        assert_eq!(parse_near(&args("5 7 27")), Some((5, 7, 27)));
+        assert_eq!(parse_near(&args("0 3 3")), Some((0, 3, 3)));
+        assert_eq!(parse_near(&args("5 27 7")), None);
+        assert_eq!(parse_near(&args("-1 7 27")), None);
+        assert_eq!(parse_near(&args("5 7")), None);
+        assert_eq!(parse_near(&args("5 7 27 30")), None);
+        assert_eq!(parse_near(&args("5 x 27")), None);
     }
 }